@@ -4,12 +4,19 @@
 #![allow(unused_mut)]
 
 use criterion::{criterion_group, criterion_main, Criterion};
-use grumpkin_msm::pasta::utils::{gen_points, gen_scalars};
+use grumpkin_msm::utils::{gen_points_seeded, gen_scalars_seeded};
+use grumpkin_msm::{set_default_backend, Backend, FixedBaseMsm, Msm};
+use halo2curves::group::Curve;
+use pasta_curves::{pallas, vesta};
 
 #[cfg(feature = "cuda")]
-use grumpkin_msm::cuda_available;
+use grumpkin_msm::gpu_available;
 
-fn criterion_benchmark(c: &mut Criterion) {
+fn bench_curve<C: Msm>(
+    c: &mut Criterion,
+    curve: &str,
+    msm: fn(&[C], &[C::Scalar]) -> C::Curve,
+) {
     let bench_npow: usize = std::env::var("BENCH_NPOW")
         .unwrap_or("17".to_string())
         .parse()
@@ -17,28 +24,83 @@ fn criterion_benchmark(c: &mut Criterion) {
     let npoints: usize = 1 << bench_npow;
 
     // println!("generating {} random points, just hang on...", npoints);
-    let mut points = gen_points(npoints);
-    let mut scalars = gen_scalars(npoints);
+    let mut points = gen_points_seeded::<C>(npoints, 0);
+    let mut scalars = gen_scalars_seeded::<C::Scalar>(npoints, 0);
 
-    #[cfg(feature = "cuda")]
-    {
-        unsafe { grumpkin_msm::CUDA_OFF = true };
-    }
+    set_default_backend(Backend::Cpu);
 
-    let mut group = c.benchmark_group("CPU");
+    let mut group = c.benchmark_group(format!("{} CPU", curve));
     group.sample_size(10);
 
     group.bench_function(format!("2**{} points", bench_npow), |b| {
         b.iter(|| {
-            let _ = grumpkin_msm::pasta::pallas(&points, &scalars);
+            let _ = msm(&points, &scalars);
         })
     });
 
+    let projective = points
+        .iter()
+        .map(|point| point.to_curve())
+        .collect::<Vec<_>>();
+    group.bench_function(
+        format!("2**{} projective points, normalize then msm", bench_npow),
+        |b| {
+            b.iter(|| {
+                let mut affine = vec![C::identity(); npoints];
+                C::Curve::batch_normalize(&projective, &mut affine);
+                let _ = msm(&affine, &scalars);
+            })
+        },
+    );
+    group.bench_function(
+        format!("2**{} projective points, msm_projective", bench_npow),
+        |b| {
+            b.iter(|| {
+                let _ = C::msm_projective(&projective, &scalars);
+            })
+        },
+    );
+
+    let fixed_base = FixedBaseMsm::new(&points);
+    group.bench_function(
+        format!("2**{} points, fixed base", bench_npow),
+        |b| {
+            b.iter(|| {
+                let _ = fixed_base.msm(&scalars);
+            })
+        },
+    );
+
+    for nbatches in [2, 4, 16] {
+        let batch = (0..nbatches)
+            .map(|i| gen_scalars_seeded::<C::Scalar>(npoints, i as u64 + 1))
+            .collect::<Vec<_>>();
+        let batch = batch.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        group.bench_function(
+            format!("2**{} points, {} msm calls", bench_npow, nbatches),
+            |b| {
+                b.iter(|| {
+                    for scalars in &batch {
+                        let _ = msm(&points, scalars);
+                    }
+                })
+            },
+        );
+        group.bench_function(
+            format!("2**{} points, batch of {}", bench_npow, nbatches),
+            |b| {
+                b.iter(|| {
+                    let _ = C::batch_msm(&points, &batch);
+                })
+            },
+        );
+    }
+
     group.finish();
 
     #[cfg(feature = "cuda")]
-    if unsafe { cuda_available() } {
-        unsafe { grumpkin_msm::CUDA_OFF = false };
+    if gpu_available() {
+        set_default_backend(Backend::Auto);
 
         const EXTRA: usize = 5;
         let bench_npow = bench_npow + EXTRA;
@@ -47,14 +109,14 @@ fn criterion_benchmark(c: &mut Criterion) {
         while points.len() < npoints {
             points.append(&mut points.clone());
         }
-        scalars.append(&mut gen_scalars(npoints - scalars.len()));
+        scalars.append(&mut gen_scalars_seeded(npoints - scalars.len(), 1));
 
-        let mut group = c.benchmark_group("GPU");
+        let mut group = c.benchmark_group(format!("{} GPU", curve));
         group.sample_size(20);
 
         group.bench_function(format!("2**{} points", bench_npow), |b| {
             b.iter(|| {
-                let _ = grumpkin_msm::pasta::pallas(&points, &scalars);
+                let _ = msm(&points, &scalars);
             })
         });
 
@@ -62,5 +124,50 @@ fn criterion_benchmark(c: &mut Criterion) {
     }
 }
 
+/// Repeated MSMs over the same generators, plain and with a precomputed
+/// [`FixedBaseMsm`], at sizes on either side of where its table stops
+/// fitting in the caches.
+fn bench_fixed_base<C: Msm>(
+    c: &mut Criterion,
+    curve: &str,
+    msm: fn(&[C], &[C::Scalar]) -> C::Curve,
+) {
+    set_default_backend(Backend::Cpu);
+
+    let mut group = c.benchmark_group(format!("{} CPU fixed base", curve));
+    group.sample_size(10);
+
+    for npow in [16, 18] {
+        let npoints: usize = 1 << npow;
+        let points = gen_points_seeded::<C>(npoints, 0);
+        let scalars = gen_scalars_seeded::<C::Scalar>(npoints, 0);
+        let fixed_base = FixedBaseMsm::new(&points);
+
+        group.bench_function(format!("2**{} points, msm", npow), |b| {
+            b.iter(|| {
+                let _ = msm(&points, &scalars);
+            })
+        });
+        group.bench_function(format!("2**{} points, fixed base", npow), |b| {
+            b.iter(|| {
+                let _ = fixed_base.msm(&scalars);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    bench_curve::<pallas::Affine>(c, "pallas", grumpkin_msm::pasta::pallas);
+    bench_curve::<vesta::Affine>(c, "vesta", grumpkin_msm::pasta::vesta);
+    bench_fixed_base::<pallas::Affine>(
+        c,
+        "pallas",
+        grumpkin_msm::pasta::pallas,
+    );
+    bench_fixed_base::<vesta::Affine>(c, "vesta", grumpkin_msm::pasta::vesta);
+}
+
 criterion_group!(benches, criterion_benchmark);
 criterion_main!(benches);
@@ -2,6 +2,10 @@ use std::env;
 use std::path::PathBuf;
 
 fn main() {
+    if !cfg!(feature = "native") {
+        return;
+    }
+
     let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
 
     compile_source(
@@ -16,6 +20,10 @@ fn main() {
         "pasta_msm",
         &target_arch,
     );
+    println!("cargo:rerun-if-changed=src/fixed_base.hpp");
+    println!("cargo:rerun-if-changed=src/pippenger_batch.hpp");
+    println!("cargo:rerun-if-changed=src/pippenger_bits.hpp");
+    println!("cargo:rerun-if-changed=src/thread_pools.hpp");
 
     if cfg!(target_os = "windows") && !cfg!(target_env = "msvc") {
         return;
@@ -66,6 +74,7 @@ fn compile_source(
     if let Some(include) = env::var_os("DEP_SPPARK_ROOT") {
         cc.include(include);
     }
+    println!("cargo:rerun-if-changed={}", file.display());
     cc.file(file).compile(output_name);
 }
 
@@ -80,7 +89,7 @@ fn common_build_configurations(cc: &mut cc::Build) {
 }
 
 fn determine_cc_def(target_arch: &str, default_def: &str) -> Option<String> {
-    assert!(
+    const _: () = assert!(
         !(cfg!(feature = "portable") && cfg!(feature = "force-adx")),
         "Cannot compile with both `portable` and `force-adx` features"
     );
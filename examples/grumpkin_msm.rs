@@ -1,8 +1,6 @@
 use grumpkin_msm::utils::{gen_points, gen_scalars, naive_multiscalar_mul};
 use halo2curves::group::Curve;
-
-#[cfg(feature = "cuda")]
-use grumpkin_msm::cuda_available;
+use halo2curves::{bn256, grumpkin};
 
 fn main() {
     let bench_npow: usize = std::env::var("BENCH_NPOW")
@@ -12,16 +10,18 @@ fn main() {
     let npoints: usize = 1 << bench_npow;
 
     println!("generating {} random points, just hang on...", npoints);
-    let points = gen_points(npoints);
+    let points = gen_points::<bn256::G1Affine>(npoints);
     let scalars = gen_scalars(npoints);
 
-    #[cfg(feature = "cuda")]
-    if unsafe { cuda_available() } {
-        unsafe { grumpkin_msm::CUDA_OFF = false };
-    }
-
     let res = grumpkin_msm::bn256(&points, &scalars).to_affine();
     let native = naive_multiscalar_mul(&points, &scalars);
     assert_eq!(res, native);
+
+    let points = gen_points::<grumpkin::G1Affine>(npoints);
+    let scalars = gen_scalars(npoints);
+
+    let res = grumpkin_msm::grumpkin(&points, &scalars).to_affine();
+    let native = naive_multiscalar_mul(&points, &scalars);
+    assert_eq!(res, native);
     println!("success!")
 }
@@ -1,10 +1,8 @@
-#[cfg(feature = "cuda")]
-use grumpkin_msm::cuda_available;
-
 use grumpkin_msm::pasta::utils::{
     gen_points, gen_scalars, naive_multiscalar_mul,
 };
 use pasta_curves::group::Curve;
+use pasta_curves::{pallas, vesta};
 
 fn main() {
     let bench_npow: usize = std::env::var("BENCH_NPOW")
@@ -14,16 +12,18 @@ fn main() {
     let npoints: usize = 1 << bench_npow;
 
     println!("generating {} random points, just hang on...", npoints);
-    let points = gen_points(npoints);
+    let points = gen_points::<pallas::Affine>(npoints);
     let scalars = gen_scalars(npoints);
 
-    #[cfg(feature = "cuda")]
-    if unsafe { cuda_available() } {
-        unsafe { grumpkin_msm::CUDA_OFF = false };
-    }
-
     let res = grumpkin_msm::pasta::pallas(&points, &scalars).to_affine();
     let native = naive_multiscalar_mul(&points, &scalars);
     assert_eq!(res, native);
+
+    let points = gen_points::<vesta::Affine>(npoints);
+    let scalars = gen_scalars(npoints);
+
+    let res = grumpkin_msm::pasta::vesta(&points, &scalars).to_affine();
+    let native = naive_multiscalar_mul(&points, &scalars);
+    assert_eq!(res, native);
     println!("success!")
 }
@@ -0,0 +1,187 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use halo2curves::group::Group;
+
+use crate::{Msm, MsmConfig, MsmError};
+
+/// Number of terms [`MsmAccumulator::try_feed_iter`] buffers before
+/// running an MSM over them.
+const ITER_CHUNK: usize = 1 << 16;
+
+/// MSM over inputs supplied a chunk at a time, for sums too large to hold
+/// all points and scalars in memory at once. Every chunk goes through the
+/// regular MSM and the partial results are added up, so the result does not
+/// depend on where the chunk boundaries fall.
+pub struct MsmAccumulator<C: Msm> {
+    acc: C::Curve,
+    len: usize,
+    config: MsmConfig,
+}
+
+impl<C: Msm> Default for MsmAccumulator<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Msm> MsmAccumulator<C> {
+    pub fn new() -> Self {
+        Self::with_config(MsmConfig::default())
+    }
+
+    /// Runs the MSM of every chunk with the options of `config`.
+    pub fn with_config(config: MsmConfig) -> Self {
+        Self {
+            acc: C::Curve::identity(),
+            len: 0,
+            config,
+        }
+    }
+
+    /// Number of terms fed so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds the MSM of `points` and `scalars` to the sum. On error the
+    /// accumulator is left as it was, with indices in the error counted
+    /// from the first term ever fed.
+    pub fn try_feed(
+        &mut self,
+        points: &[C],
+        scalars: &[C::Scalar],
+    ) -> Result<(), MsmError> {
+        let ret = C::try_msm_with_config(points, scalars, &self.config)
+            .map_err(|err| match err {
+                MsmError::InvalidPoint { index } => MsmError::InvalidPoint {
+                    index: self.len + index,
+                },
+                err => err,
+            })?;
+        self.acc += ret;
+        self.len += points.len();
+        Ok(())
+    }
+
+    pub fn feed(&mut self, points: &[C], scalars: &[C::Scalar]) {
+        self.try_feed(points, scalars)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Feeds the terms of `iter`, buffering them into chunks of 2^16. On
+    /// error the terms of the failing chunk are not added, but those of
+    /// earlier chunks are.
+    pub fn try_feed_iter(
+        &mut self,
+        iter: impl IntoIterator<Item = (C, C::Scalar)>,
+    ) -> Result<(), MsmError> {
+        let mut iter = iter.into_iter();
+        let mut points = Vec::with_capacity(ITER_CHUNK);
+        let mut scalars = Vec::with_capacity(ITER_CHUNK);
+        loop {
+            points.clear();
+            scalars.clear();
+            for (point, scalar) in iter.by_ref().take(ITER_CHUNK) {
+                points.push(point);
+                scalars.push(scalar);
+            }
+            if points.is_empty() {
+                return Ok(());
+            }
+            self.try_feed(&points, &scalars)?;
+        }
+    }
+
+    /// Returns the MSM of all terms fed.
+    pub fn finish(self) -> C::Curve {
+        self.acc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256;
+    use halo2curves::grumpkin;
+    use pasta_curves::{pallas, vesta};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    use super::*;
+    use crate::utils::{gen_points_seeded, gen_scalars_seeded, off_curve};
+    use crate::Threads;
+
+    fn check_accumulator<C: Msm>() {
+        let mut rng = ChaCha20Rng::seed_from_u64(21);
+        let mut points = gen_points_seeded::<C>(3000, 21);
+        let scalars = gen_scalars_seeded::<C::Scalar>(3000, 21);
+        let expected = C::msm(&points, &scalars);
+
+        for threads in [Threads::Default, Threads::Rayon] {
+            let config = MsmConfig {
+                threads,
+                ..Default::default()
+            };
+            for _ in 0..5 {
+                let mut acc = MsmAccumulator::<C>::with_config(config);
+                let mut start = 0;
+                while start < points.len() {
+                    let end =
+                        rng.gen_range(start..=points.len().min(start + 1000));
+                    acc.feed(&points[start..end], &scalars[start..end]);
+                    start = end;
+                    assert_eq!(acc.len(), start);
+                }
+                assert_eq!(acc.finish(), expected, "{:?}", threads);
+            }
+        }
+
+        let mut acc = MsmAccumulator::<C>::new();
+        assert!(acc.is_empty());
+        for i in 0..points.len() {
+            acc.feed(&points[i..i + 1], &scalars[i..i + 1]);
+        }
+        assert_eq!(acc.finish(), expected);
+
+        let mut acc = MsmAccumulator::<C>::new();
+        acc.try_feed_iter(points.iter().copied().zip(scalars.iter().copied()))
+            .unwrap();
+        acc.try_feed_iter([]).unwrap();
+        assert_eq!(acc.len(), points.len());
+        assert_eq!(acc.finish(), expected);
+
+        let mut acc = MsmAccumulator::<C>::with_config(MsmConfig {
+            validate: true,
+            ..Default::default()
+        });
+        acc.feed(&points[..100], &scalars[..100]);
+        assert_eq!(
+            acc.try_feed(&points[100..], &scalars[101..]),
+            Err(MsmError::LengthMismatch {
+                points: 2900,
+                scalars: 2899
+            })
+        );
+        points[150] = off_curve(points[150]);
+        assert_eq!(
+            acc.try_feed(&points[100..], &scalars[100..]),
+            Err(MsmError::InvalidPoint { index: 150 })
+        );
+        acc.feed(&points[100..150], &scalars[100..150]);
+        assert_eq!(acc.len(), 150);
+        assert_eq!(acc.finish(), C::msm(&points[..150], &scalars[..150]));
+    }
+
+    #[test]
+    fn accumulator_matches_msm() {
+        check_accumulator::<bn256::G1Affine>();
+        check_accumulator::<grumpkin::G1Affine>();
+        check_accumulator::<pallas::Affine>();
+        check_accumulator::<vesta::Affine>();
+    }
+}
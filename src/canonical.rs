@@ -0,0 +1,134 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::cmp::Ordering;
+
+use halo2curves::ff::{Field, PrimeField};
+use rayon::prelude::*;
+
+use crate::config::{self, MsmConfig, Threads};
+use crate::error::check_lengths;
+use crate::{ffi, small, Msm, MsmError};
+
+pub(crate) fn try_msm<C: Msm>(
+    points: &[C],
+    scalars: &[[u64; 4]],
+    config: &MsmConfig,
+) -> Result<C::Curve, MsmError> {
+    let npoints = check_lengths(points.len(), scalars.len())?;
+    config.check_points(points)?;
+
+    // Scalars are fed to Pippenger as they are, whose windows only cover
+    // the width of the modulus.
+    let max = limbs(&(-C::Scalar::ONE));
+    if let Some(index) = scalars.par_iter().position_first(|scalar| {
+        scalar.iter().rev().cmp(max.iter().rev()) == Ordering::Greater
+    }) {
+        return Err(MsmError::NonCanonicalScalar { index });
+    }
+
+    if config.is_small(npoints) {
+        let scalars = as_bytes(scalars)
+            .chunks(32)
+            .map(|bytes| {
+                let mut repr = <C::Scalar as PrimeField>::Repr::default();
+                repr.as_mut().copy_from_slice(bytes);
+                C::Scalar::from_repr(repr).unwrap()
+            })
+            .collect::<Vec<_>>();
+        return Ok(small::msm(points, &scalars));
+    }
+
+    match config.threads {
+        Threads::Rayon => config::par_ranges(npoints, |range| {
+            ffi::mult_pippenger_canonical(
+                &points[range.clone()],
+                &scalars[range],
+                1,
+            )
+        }),
+        threads => {
+            ffi::mult_pippenger_canonical(points, scalars, threads.nthreads())
+        }
+    }
+}
+
+/// Canonical limbs of a 256-bit field element.
+pub(crate) fn limbs<F: PrimeField>(scalar: &F) -> [u64; 4] {
+    let mut limbs = [0u64; 4];
+    for (limb, bytes) in
+        limbs.iter_mut().zip(scalar.to_repr().as_ref().chunks(8))
+    {
+        *limb = u64::from_le_bytes(bytes.try_into().unwrap());
+    }
+    limbs
+}
+
+pub(crate) fn as_bytes(scalars: &[[u64; 4]]) -> &[u8] {
+    // Like the C++ side, this relies on a little-endian host, where limbs
+    // are laid out as the canonical 32-byte encoding.
+    unsafe {
+        std::slice::from_raw_parts(
+            scalars.as_ptr() as *const u8,
+            32 * scalars.len(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256;
+    use halo2curves::group::Curve;
+    use halo2curves::grumpkin;
+    use pasta_curves::{pallas, vesta};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    use super::*;
+    use crate::utils::{gen_points_seeded, gen_scalars_seeded};
+
+    fn check_canonical<C: Msm>() {
+        for n in [0, 3, 1000] {
+            let points = gen_points_seeded::<C>(n, 15);
+            let mut scalars = gen_scalars_seeded::<C::Scalar>(n, 15);
+            if n > 0 {
+                scalars[0] = -C::Scalar::ONE;
+            }
+            let canonical = scalars.iter().map(limbs).collect::<Vec<_>>();
+
+            let expected = C::msm(&points, &scalars);
+            assert_eq!(C::msm_canonical(&points, &canonical), expected);
+            assert_eq!(
+                C::try_msm_canonical_with_config(
+                    &points,
+                    &canonical,
+                    &MsmConfig {
+                        threads: Threads::Rayon,
+                        ..Default::default()
+                    }
+                ),
+                Ok(expected)
+            );
+        }
+
+        let points = gen_points_seeded::<C>(100, 15);
+        let mut canonical = vec![[1, 0, 0, 0]; 100];
+        let mut modulus = limbs(&(-C::Scalar::ONE));
+        modulus[0] += 1;
+        canonical[42] = modulus;
+        canonical[77] = [u64::MAX; 4];
+        assert_eq!(
+            C::try_msm_canonical(&points, &canonical),
+            Err(MsmError::NonCanonicalScalar { index: 42 })
+        );
+    }
+
+    #[test]
+    fn canonical_matches_msm() {
+        check_canonical::<bn256::G1Affine>();
+        check_canonical::<grumpkin::G1Affine>();
+        check_canonical::<pallas::Affine>();
+        check_canonical::<vesta::Affine>();
+    }
+}
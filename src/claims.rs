@@ -0,0 +1,175 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use halo2curves::ff::Field;
+use halo2curves::group::Group;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::error::check_lengths;
+use crate::{Msm, MsmError};
+
+/// Claim that the MSM of `points` and `scalars` equals `result`.
+#[derive(Clone, Copy, Debug)]
+pub struct MsmClaim<'a, C: Msm> {
+    pub points: &'a [C],
+    pub scalars: &'a [C::Scalar],
+    pub result: C,
+}
+
+/// Checks all `claims` with a single MSM over the claims combined with
+/// random weights, `sum_j w_j * (sum_i s_ij * P_ij - Q_j) == 0`. A false
+/// claim is accepted with probability about `1 / r`, `r` being the scalar
+/// field order, and true claims are never rejected.
+pub fn verify_claims<C: Msm>(
+    claims: &[MsmClaim<'_, C>],
+) -> Result<bool, MsmError> {
+    verify_claims_with_rng(claims, &mut ChaCha20Rng::from_entropy())
+}
+
+/// Like [`verify_claims`], drawing the weights from `rng`, which must not be
+/// predictable by whoever produced the claims.
+pub fn verify_claims_with_rng<C: Msm>(
+    claims: &[MsmClaim<'_, C>],
+    rng: &mut impl RngCore,
+) -> Result<bool, MsmError> {
+    for claim in claims {
+        check_lengths(claim.points.len(), claim.scalars.len())?;
+    }
+    if let [claim] = claims {
+        return Ok(
+            C::try_msm(claim.points, claim.scalars)? == claim.result.into()
+        );
+    }
+
+    let len = claims.iter().map(|claim| claim.points.len() + 1).sum();
+    let mut points = Vec::with_capacity(len);
+    let mut scalars = Vec::with_capacity(len);
+    for claim in claims {
+        let weight = C::Scalar::random(&mut *rng);
+        points.extend_from_slice(claim.points);
+        scalars.extend(claim.scalars.iter().map(|scalar| *scalar * weight));
+        points.push(claim.result);
+        scalars.push(-weight);
+    }
+
+    Ok(bool::from(C::try_msm(&points, &scalars)?.is_identity()))
+}
+
+/// Returns the index of the first false claim, if any. The claims are
+/// checked together as by [`verify_claims`] and, if that fails, halved
+/// until the false claim is isolated, at a cost of about
+/// `log2(claims.len())` more combined checks.
+pub fn find_invalid_claim<C: Msm>(
+    claims: &[MsmClaim<'_, C>],
+) -> Result<Option<usize>, MsmError> {
+    find_invalid_claim_with_rng(claims, &mut ChaCha20Rng::from_entropy())
+}
+
+/// Like [`find_invalid_claim`], drawing the weights from `rng`.
+pub fn find_invalid_claim_with_rng<C: Msm>(
+    claims: &[MsmClaim<'_, C>],
+    rng: &mut impl RngCore,
+) -> Result<Option<usize>, MsmError> {
+    if verify_claims_with_rng(claims, rng)? {
+        return Ok(None);
+    }
+    bisect(claims, rng).map(Some)
+}
+
+/// Finds the first false claim among `claims`, known to contain one.
+fn bisect<C: Msm>(
+    claims: &[MsmClaim<'_, C>],
+    rng: &mut impl RngCore,
+) -> Result<usize, MsmError> {
+    if claims.len() == 1 {
+        return Ok(0);
+    }
+    let (left, right) = claims.split_at(claims.len() / 2);
+    if !verify_claims_with_rng(left, rng)? {
+        return bisect(left, rng);
+    }
+    Ok(left.len() + bisect(right, rng)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256;
+    use halo2curves::group::Curve;
+    use halo2curves::grumpkin;
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+    use crate::utils::{gen_points_seeded, gen_scalars_seeded};
+
+    fn check_claims<C: Msm>() {
+        let mut rng = ChaCha20Rng::seed_from_u64(25);
+        let sizes = [0, 1, 2, 5, 9, 30, 100, 3, 0, 17, 64, 8, 1, 40, 7, 250];
+        let points = gen_points_seeded::<C>(250, 25);
+        let scalars = sizes
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| gen_scalars_seeded::<C::Scalar>(n, i as u64))
+            .collect::<Vec<_>>();
+        let valid = scalars
+            .iter()
+            .map(|scalars| MsmClaim {
+                points: &points[..scalars.len()],
+                scalars,
+                result: C::msm(&points[..scalars.len()], scalars).to_affine(),
+            })
+            .collect::<Vec<_>>();
+
+        let verify = |claims: &[MsmClaim<'_, C>], rng: &mut ChaCha20Rng| {
+            verify_claims_with_rng(claims, rng).unwrap()
+        };
+        let find = |claims: &[MsmClaim<'_, C>], rng: &mut ChaCha20Rng| {
+            find_invalid_claim_with_rng(claims, rng).unwrap()
+        };
+        assert!(verify(&valid, &mut rng));
+        assert!(verify(&valid[3..4], &mut rng));
+        assert!(verify(&[], &mut rng));
+        assert_eq!(find(&valid, &mut rng), None);
+        assert!(verify_claims(&valid).unwrap());
+
+        let offset = C::generator();
+        let shift = |claim: &MsmClaim<'_, C>, by: C::Curve| {
+            (claim.result.to_curve() + by).to_affine()
+        };
+        for bad in [0, 7, 8, 15] {
+            let mut claims = valid.clone();
+            claims[bad].result = shift(&claims[bad], offset.into());
+            assert!(!verify(&claims, &mut rng), "bad = {}", bad);
+            assert!(!verify(&claims[bad..bad + 1], &mut rng));
+            assert_eq!(find(&claims, &mut rng), Some(bad));
+            assert_eq!(find_invalid_claim(&claims).unwrap(), Some(bad));
+        }
+
+        // Errors that cancel out in a plain sum are caught with weights.
+        let mut claims = valid.clone();
+        claims[4].result = shift(&claims[4], offset.into());
+        claims[11].result = shift(&claims[11], -C::Curve::from(offset));
+        assert!(!verify(&claims, &mut rng));
+        assert_eq!(find(&claims, &mut rng), Some(4));
+        assert_eq!(find(&claims[5..], &mut rng), Some(6));
+
+        let mut claims = valid.clone();
+        claims[9].scalars = &claims[9].scalars[1..];
+        assert_eq!(
+            verify_claims_with_rng(&claims, &mut rng),
+            Err(MsmError::LengthMismatch {
+                points: 17,
+                scalars: 16
+            })
+        );
+    }
+
+    #[test]
+    fn batch_verification() {
+        check_claims::<bn256::G1Affine>();
+        check_claims::<grumpkin::G1Affine>();
+        check_claims::<pallas::Affine>();
+        check_claims::<vesta::Affine>();
+    }
+}
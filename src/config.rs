@@ -0,0 +1,190 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::ops::Range;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use halo2curves::group::Group;
+use halo2curves::CurveAffine;
+use rayon::prelude::*;
+
+use crate::error::check_points;
+use crate::small::small_msm_threshold;
+use crate::MsmError;
+
+/// Default number of points from which [`Backend::Auto`] picks the GPU.
+pub const DEFAULT_GPU_THRESHOLD: usize = 1 << 16;
+
+static GPU_THRESHOLD: AtomicUsize = AtomicUsize::new(DEFAULT_GPU_THRESHOLD);
+static DEFAULT_BACKEND: AtomicU8 = AtomicU8::new(Backend::Auto as u8);
+
+/// Backend an MSM runs on. Only full-width scalar MSMs have a GPU
+/// implementation, the narrow and byte-string entry points always run on
+/// the CPU.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Backend {
+    /// The GPU for inputs of at least [`gpu_threshold`] points, if one is
+    /// available, and the CPU otherwise.
+    #[default]
+    Auto,
+    /// Always the CPU.
+    Cpu,
+    /// The GPU for all inputs not served by the small-MSM path, see
+    /// [`crate::small_msm_threshold`]. Fails with
+    /// [`MsmError::GpuUnavailable`] if the crate was built without CUDA
+    /// support or no device was found.
+    Gpu,
+}
+
+/// Whether the crate was built with CUDA support and a device was found.
+pub fn gpu_available() -> bool {
+    #[cfg(feature = "cuda")]
+    return unsafe { crate::cuda_available() };
+    #[cfg(not(feature = "cuda"))]
+    return false;
+}
+
+/// Returns the backend used by calls that do not pick one themselves.
+pub fn default_backend() -> Backend {
+    match DEFAULT_BACKEND.load(Ordering::Relaxed) {
+        x if x == Backend::Cpu as u8 => Backend::Cpu,
+        x if x == Backend::Gpu as u8 => Backend::Gpu,
+        _ => Backend::Auto,
+    }
+}
+
+/// Sets the backend used by calls that do not pick one themselves.
+pub fn set_default_backend(backend: Backend) {
+    DEFAULT_BACKEND.store(backend as u8, Ordering::Relaxed);
+}
+
+/// Returns the number of points from which [`Backend::Auto`] picks the GPU,
+/// for calls that do not set their own.
+pub fn gpu_threshold() -> usize {
+    GPU_THRESHOLD.load(Ordering::Relaxed)
+}
+
+/// Sets the number of points from which [`Backend::Auto`] picks the GPU,
+/// for calls that do not set their own.
+pub fn set_gpu_threshold(threshold: usize) {
+    GPU_THRESHOLD.store(threshold, Ordering::Relaxed);
+}
+
+/// Threads the CPU backend runs an MSM on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Threads {
+    /// The backend's process-wide pool, with one thread per available CPU.
+    /// With the `pure-rust` backend, the current rayon pool.
+    #[default]
+    Default,
+    /// A dedicated pool of this many threads, created on first use and kept
+    /// until the process exits for later calls asking for the same count.
+    /// Counts of at least the number of available CPUs use the default pool
+    /// instead, so at most one pool per smaller count is ever created.
+    /// `Count(1)` runs on the calling thread alone and `Count(0)` is the
+    /// same as `Default`.
+    Count(usize),
+    /// The current rayon pool, i.e. the caller's own when invoked from
+    /// within [`rayon::ThreadPool::install`]. The points are split into one
+    /// chunk per rayon thread, each summed up single-threaded.
+    Rayon,
+}
+
+impl Threads {
+    /// Thread count handed to the C++ backend, where 0 selects its default
+    /// pool and 1 the calling thread.
+    pub(crate) fn nthreads(self) -> usize {
+        match self {
+            Self::Default => 0,
+            Self::Count(n) => n,
+            Self::Rayon => 1,
+        }
+    }
+}
+
+/// Options of the `*_with_config` entry points of [`crate::Msm`]. The
+/// default matches the behaviour of the plain entry points.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MsmConfig {
+    pub threads: Threads,
+    /// Overrides [`default_backend`] for this call.
+    pub backend: Option<Backend>,
+    /// Overrides [`gpu_threshold`] for this call.
+    pub gpu_threshold: Option<usize>,
+    /// Overrides [`small_msm_threshold`] for this call.
+    pub small_msm_threshold: Option<usize>,
+    /// Checks that all points are on the curve before dispatching, as the
+    /// backends compute garbage for points that are not, e.g. ones read
+    /// from an untrusted commitment key.
+    pub validate: bool,
+}
+
+impl MsmConfig {
+    /// Checks `points` if [`MsmConfig::validate`] is set.
+    pub(crate) fn check_points<C: CurveAffine>(
+        &self,
+        points: &[C],
+    ) -> Result<(), MsmError> {
+        if self.validate {
+            check_points(points)?;
+        }
+        Ok(())
+    }
+
+    /// Whether an MSM over `npoints` points takes the small-MSM path. Empty
+    /// inputs always do, since the C++ implementation cannot handle them.
+    pub(crate) fn is_small(&self, npoints: usize) -> bool {
+        npoints == 0
+            || npoints
+                < self.small_msm_threshold.unwrap_or_else(small_msm_threshold)
+    }
+
+    /// Whether an MSM over `npoints` points goes to the GPU.
+    pub(crate) fn use_gpu(&self, npoints: usize) -> Result<bool, MsmError> {
+        match self.backend.unwrap_or_else(default_backend) {
+            Backend::Cpu => Ok(false),
+            Backend::Gpu if gpu_available() => Ok(true),
+            Backend::Gpu => Err(MsmError::GpuUnavailable),
+            Backend::Auto => Ok(gpu_available()
+                && npoints >= self.gpu_threshold.unwrap_or_else(gpu_threshold)),
+        }
+    }
+}
+
+/// Splits `0..npoints` into one range per rayon thread, hands each to
+/// `msm` on the current rayon pool and sums up the results.
+pub(crate) fn par_ranges<G: Group + Send>(
+    npoints: usize,
+    msm: impl Fn(Range<usize>) -> Result<G, MsmError> + Send + Sync,
+) -> Result<G, MsmError> {
+    let nchunks = rayon::current_num_threads().clamp(1, npoints.max(1));
+    let chunk = (npoints + nchunks - 1) / nchunks;
+    (0..nchunks)
+        .into_par_iter()
+        .map(|i| i * chunk..npoints.min((i + 1) * chunk))
+        .filter(|range| !range.is_empty())
+        .map(&msm)
+        .reduce(|| Ok(G::identity()), |a, b| Ok(a? + b?))
+}
+
+/// Like [`par_ranges`], for `msm` returning one result per vector of a
+/// batch of `nbatches`, summed up element-wise.
+pub(crate) fn par_ranges_batch<G: Group + Send>(
+    npoints: usize,
+    nbatches: usize,
+    msm: impl Fn(Range<usize>) -> Result<Vec<G>, MsmError> + Send + Sync,
+) -> Result<Vec<G>, MsmError> {
+    let nchunks = rayon::current_num_threads().clamp(1, npoints.max(1));
+    let chunk = (npoints + nchunks - 1) / nchunks;
+    (0..nchunks)
+        .into_par_iter()
+        .map(|i| i * chunk..npoints.min((i + 1) * chunk))
+        .filter(|range| !range.is_empty())
+        .map(&msm)
+        .reduce(
+            || Ok(vec![G::identity(); nbatches]),
+            |a, b| Ok(a?.into_iter().zip(b?).map(|(a, b)| a + b).collect()),
+        )
+}
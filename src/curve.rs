@@ -0,0 +1,43 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt;
+
+/// Identifier of a supported curve, as stored in the headers of the file
+/// formats of this crate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum CurveId {
+    Bn256 = 1,
+    Grumpkin = 2,
+    Pallas = 3,
+    Vesta = 4,
+}
+
+impl CurveId {
+    pub fn from_u32(id: u32) -> Option<Self> {
+        match id {
+            1 => Some(Self::Bn256),
+            2 => Some(Self::Grumpkin),
+            3 => Some(Self::Pallas),
+            4 => Some(Self::Vesta),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bn256 => "bn256",
+            Self::Grumpkin => "grumpkin",
+            Self::Pallas => "pallas",
+            Self::Vesta => "vesta",
+        }
+    }
+}
+
+impl fmt::Display for CurveId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt;
+
+use halo2curves::CurveAffine;
+use rayon::prelude::*;
+
+/// Errors reported by the fallible `try_*` MSM entry points.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MsmError {
+    /// `points` and `scalars` have different lengths.
+    LengthMismatch { points: usize, scalars: usize },
+    /// The scalar at `index` does not fit in the declared `max_bits`.
+    ScalarTooWide { index: usize, max_bits: usize },
+    /// The declared scalar width `nbits` exceeds the `max_bits` of the
+    /// scalar field.
+    WidthTooLarge { nbits: usize, max_bits: usize },
+    /// The point at `index` is not on the curve.
+    InvalidPoint { index: usize },
+    /// The canonical scalar at `index` is not less than the modulus.
+    NonCanonicalScalar { index: usize },
+    /// The blinding generator of a commitment key is the identity or, up to
+    /// sign, one of the value generators, so commitments would not hide.
+    WeakBlinding,
+    /// The backend returned a point that is not on the curve.
+    InvalidOutput,
+    /// The GPU backend was requested, but is not available.
+    GpuUnavailable,
+    /// The GPU backend failed with the given error code and message.
+    Backend { code: i32, message: String },
+}
+
+impl fmt::Display for MsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LengthMismatch { points, scalars } => write!(
+                f,
+                "length mismatch: {} points, {} scalars",
+                points, scalars
+            ),
+            Self::ScalarTooWide { index, max_bits } => {
+                write!(f, "scalar #{} does not fit in {} bits", index, max_bits)
+            }
+            Self::WidthTooLarge { nbits, max_bits } => write!(
+                f,
+                "{}-bit scalars exceed the {}-bit scalar field",
+                nbits, max_bits
+            ),
+            Self::InvalidPoint { index } => {
+                write!(f, "point #{} is not on the curve", index)
+            }
+            Self::NonCanonicalScalar { index } => {
+                write!(f, "scalar #{} is not less than the modulus", index)
+            }
+            Self::WeakBlinding => {
+                write!(f, "blinding generator is not independent")
+            }
+            Self::InvalidOutput => {
+                write!(f, "backend returned a point off the curve")
+            }
+            Self::GpuUnavailable => write!(f, "GPU backend is not available"),
+            Self::Backend { code, message } => {
+                write!(f, "backend error #{}: {}", code, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MsmError {}
+
+#[cfg(feature = "cuda")]
+impl From<crate::cuda::Error> for MsmError {
+    fn from(err: crate::cuda::Error) -> Self {
+        Self::Backend {
+            code: err.code,
+            message: String::from(&err),
+        }
+    }
+}
+
+/// Checks that `points` and `scalars` are of equal length and returns that
+/// length.
+pub(crate) fn check_lengths(
+    npoints: usize,
+    nscalars: usize,
+) -> Result<usize, MsmError> {
+    if npoints != nscalars {
+        return Err(MsmError::LengthMismatch {
+            points: npoints,
+            scalars: nscalars,
+        });
+    }
+    Ok(npoints)
+}
+
+/// Checks that `scalars` holds one `nbits`-wide byte string per point and
+/// returns the number of points.
+pub(crate) fn check_byte_lengths(
+    npoints: usize,
+    nbytes: usize,
+    nbits: usize,
+) -> Result<usize, MsmError> {
+    let stride = (nbits + 7) / 8;
+    if npoints * stride != nbytes {
+        return Err(MsmError::LengthMismatch {
+            points: npoints,
+            scalars: nbytes.checked_div(stride).unwrap_or(0),
+        });
+    }
+    Ok(npoints)
+}
+
+/// Checks that every point is on the curve, the identity being encoded as
+/// `(0, 0)`. All supported curves have prime order, so this also places
+/// the points in the prime-order subgroup.
+pub(crate) fn check_points<C: CurveAffine>(
+    points: &[C],
+) -> Result<(), MsmError> {
+    match points
+        .par_iter()
+        .position_first(|point| !bool::from(point.is_on_curve()))
+    {
+        Some(index) => Err(MsmError::InvalidPoint { index }),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use halo2curves::ff::PrimeField;
+use halo2curves::{CurveAffine, CurveExt};
+
+#[cfg(feature = "pure-rust")]
+use crate::pippenger::with_threads;
+use crate::MsmError;
+
+/// Raw backend entry points of a supported curve, with the output already
+/// converted to `Self::Curve` by [`from_jacobian`]. Kept in a private module
+/// so that [`crate::Msm`] cannot be implemented outside of this crate.
+/// Without the `native` feature there is no C++ backend and the trait is
+/// just a seal.
+pub trait Pippenger: CurveAffine {
+    /// # Safety
+    ///
+    /// `points` must be non-empty and as long as `scalars`.
+    #[cfg(feature = "native")]
+    unsafe fn mult_pippenger(
+        points: &[Self],
+        scalars: &[Self::Scalar],
+        nthreads: usize,
+    ) -> Result<Self::Curve, MsmError>;
+
+    /// Same as [`Pippenger::mult_pippenger`], with scalars given as
+    /// canonical little-endian limbs rather than in Montgomery form.
+    ///
+    /// # Safety
+    ///
+    /// `points` must be non-empty and as long as `scalars`, and every scalar
+    /// must be less than the modulus.
+    #[cfg(feature = "native")]
+    unsafe fn mult_pippenger_canonical(
+        points: &[Self],
+        scalars: &[[u64; 4]],
+        nthreads: usize,
+    ) -> Result<Self::Curve, MsmError>;
+
+    /// # Safety
+    ///
+    /// `points` must be non-empty, `nbits` non-zero and `scalars` must hold
+    /// `(nbits + 7) / 8` bytes per point.
+    #[cfg(feature = "native")]
+    unsafe fn mult_pippenger_bits(
+        points: &[Self],
+        scalars: &[u8],
+        nbits: usize,
+        nthreads: usize,
+    ) -> Result<Self::Curve, MsmError>;
+
+    /// # Safety
+    ///
+    /// `points` must be non-empty and as long as every vector of `scalars`.
+    #[cfg(feature = "native")]
+    unsafe fn mult_pippenger_batch(
+        points: &[Self],
+        scalars: &[&[Self::Scalar]],
+        nthreads: usize,
+    ) -> Result<Vec<Self::Curve>, MsmError>;
+
+    /// Fixed-base MSM over `table`, holding `ncopies` shifted copies of
+    /// every generator, with `ncopies * nrounds` signed digits of `window`
+    /// bits per generator, as laid out by [`crate::FixedBaseMsm`].
+    ///
+    /// # Safety
+    ///
+    /// `table` must be non-empty and `digits` must hold `nrounds` digits
+    /// per point of `table`.
+    #[cfg(feature = "native")]
+    unsafe fn mult_fixed_base(
+        table: &[Self],
+        ncopies: usize,
+        digits: &[i32],
+        window: usize,
+        nrounds: usize,
+        nthreads: usize,
+    ) -> Result<Self::Curve, MsmError>;
+
+    /// # Safety
+    ///
+    /// Same as [`Pippenger::mult_pippenger`].
+    #[cfg(feature = "cuda")]
+    unsafe fn cuda_pippenger(
+        points: &[Self],
+        scalars: &[Self::Scalar],
+    ) -> Result<Self::Curve, MsmError>;
+}
+
+/// Rebuilds a point from the Jacobian coordinates written by a backend,
+/// rejecting it if it is not on the curve. A zero `z` is the identity.
+pub(crate) fn from_jacobian<G: CurveExt>(
+    (x, y, z): (G::Base, G::Base, G::Base),
+) -> Result<G, MsmError> {
+    Option::from(G::new_jacobian(x, y, z)).ok_or(MsmError::InvalidOutput)
+}
+
+// The CPU backend the crate was built with. The `pure-rust` one takes
+// precedence, so that with both features enabled the C++ entry points are
+// only there to cross-check against. Either way `nthreads` is as for the
+// C++ `select_pool`.
+
+pub(crate) fn mult_pippenger<C: Pippenger>(
+    points: &[C],
+    scalars: &[C::Scalar],
+    nthreads: usize,
+) -> Result<C::Curve, MsmError> {
+    assert!(!points.is_empty() && points.len() == scalars.len());
+    #[cfg(feature = "pure-rust")]
+    return Ok(with_threads(nthreads, |parallel| {
+        crate::pippenger::msm(points, scalars, parallel)
+    }));
+    #[cfg(not(feature = "pure-rust"))]
+    return unsafe { C::mult_pippenger(points, scalars, nthreads) };
+}
+
+pub(crate) fn mult_pippenger_canonical<C: Pippenger>(
+    points: &[C],
+    scalars: &[[u64; 4]],
+    nthreads: usize,
+) -> Result<C::Curve, MsmError> {
+    assert!(!points.is_empty() && points.len() == scalars.len());
+    #[cfg(feature = "pure-rust")]
+    return Ok(with_threads(nthreads, |parallel| {
+        crate::pippenger::msm_bytes(
+            points,
+            crate::canonical::as_bytes(scalars),
+            C::Scalar::NUM_BITS as usize,
+            parallel,
+        )
+    }));
+    #[cfg(not(feature = "pure-rust"))]
+    return unsafe { C::mult_pippenger_canonical(points, scalars, nthreads) };
+}
+
+pub(crate) fn mult_pippenger_bits<C: Pippenger>(
+    points: &[C],
+    scalars: &[u8],
+    nbits: usize,
+    nthreads: usize,
+) -> Result<C::Curve, MsmError> {
+    assert!(!points.is_empty() && nbits != 0);
+    assert_eq!(points.len() * ((nbits + 7) / 8), scalars.len());
+    #[cfg(feature = "pure-rust")]
+    return Ok(with_threads(nthreads, |parallel| {
+        crate::pippenger::msm_bytes(points, scalars, nbits, parallel)
+    }));
+    #[cfg(not(feature = "pure-rust"))]
+    return unsafe { C::mult_pippenger_bits(points, scalars, nbits, nthreads) };
+}
+
+pub(crate) fn mult_pippenger_batch<C: Pippenger>(
+    points: &[C],
+    scalars: &[&[C::Scalar]],
+    nthreads: usize,
+) -> Result<Vec<C::Curve>, MsmError> {
+    assert!(!points.is_empty());
+    assert!(scalars.iter().all(|s| s.len() == points.len()));
+    #[cfg(feature = "pure-rust")]
+    return Ok(with_threads(nthreads, |parallel| {
+        crate::pippenger::msm_batch(points, scalars, parallel)
+    }));
+    #[cfg(not(feature = "pure-rust"))]
+    return unsafe { C::mult_pippenger_batch(points, scalars, nthreads) };
+}
+
+pub(crate) fn mult_fixed_base<C: Pippenger>(
+    table: &[C],
+    ncopies: usize,
+    digits: &[i32],
+    window: usize,
+    nrounds: usize,
+    nthreads: usize,
+) -> Result<C::Curve, MsmError> {
+    assert!(!table.is_empty() && table.len() % ncopies == 0);
+    assert!(window != 0 && table.len() * nrounds == digits.len());
+    #[cfg(feature = "pure-rust")]
+    return Ok(with_threads(nthreads, |parallel| {
+        crate::pippenger::fixed_base(
+            table, ncopies, digits, window, nrounds, parallel,
+        )
+    }));
+    #[cfg(not(feature = "pure-rust"))]
+    return unsafe {
+        C::mult_fixed_base(table, ncopies, digits, window, nrounds, nthreads)
+    };
+}
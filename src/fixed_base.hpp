@@ -0,0 +1,102 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+#ifndef __GRUMPKIN_MSM_FIXED_BASE_HPP__
+#define __GRUMPKIN_MSM_FIXED_BASE_HPP__
+
+#include <msm/pippenger.hpp>
+
+/*
+ * Sum of buckets[b]*(b+1+offset) for b in [0, nbuckets), zeroing the
+ * buckets along the way.
+ */
+template <class point_t, class bucket_t>
+static void integrate_range(point_t& out, bucket_t buckets[],
+                            size_t nbuckets, size_t offset)
+{
+    bucket_t acc, sum;
+
+    acc.inf();
+    sum.inf();
+    while (nbuckets--) {
+        acc.add(buckets[nbuckets]);
+        sum.add(acc);
+        buckets[nbuckets].inf();
+    }
+
+    /* |acc| is the plain sum of the range, to be added |offset| times */
+    point_t total, step = acc;
+    total.inf();
+    for (size_t bit = num_bits(offset); bit--;) {
+        total.dbl();
+        if ((offset >> bit) & 1)
+            total.add(step);
+    }
+    out = sum;
+    out.add(total);
+}
+
+/*
+ * Fixed-base MSM over a table of |ncopies| shifted copies of every
+ * generator, table[i*ncopies + t] = 2^(t*nrounds*window) * G_i, with the
+ * scalars recoded into signed digits of magnitude at most 2^(window-1),
+ * digits[i*ncopies*nrounds + j]. Digit j goes with copy j/nrounds in round
+ * j%nrounds. Each round is a single bucket pass over all copies, so the
+ * bucket sums are paid for once per round rather than once per window,
+ * and with a copy for every digit there are no doublings at all. Threads
+ * split the bucket range, rather than the points, for the same reason.
+ */
+template <class bucket_t, class point_t,
+          class affine_t = class bucket_t::affine_t>
+static void mult_fixed_base(point_t& ret, const affine_t table[],
+                            size_t npoints, size_t ncopies, const int digits[],
+                            size_t window, size_t nrounds,
+                            thread_pool_t* da_pool = nullptr)
+{
+    size_t nbuckets = (size_t)1 << (window - 1);
+    size_t ndigits = ncopies * nrounds;
+    size_t ncpus = da_pool ? da_pool->size() : 0;
+    size_t nparts = 1;
+    if (ncpus > 1 && npoints * ncopies >= 1024)
+        nparts = std::min(ncpus, nbuckets);
+
+    std::vector<point_t> parts(nparts);
+    auto run = [&](size_t part) {
+        /* buckets[b] collects the digits of magnitude lo+b+1 */
+        size_t lo = nbuckets * part / nparts,
+               hi = nbuckets * (part + 1) / nparts;
+        std::vector<bucket_t> buckets(hi - lo); /* zeroed */
+        point_t& out = parts[part];
+        point_t p;
+
+        out.inf();
+        for (size_t q = nrounds; q--;) {
+            for (size_t i = 0; i < window; i++)
+                out.dbl();
+            for (size_t i = 0; i < npoints; i++) {
+                const int* d = &digits[i * ndigits + q];
+                const affine_t* point = &table[i * ncopies];
+                for (size_t t = 0; t < ncopies; t++, d += nrounds) {
+                    int digit = *d;
+                    size_t b = (size_t)(digit < 0 ? -digit : digit) - 1 - lo;
+                    if (b < hi - lo)
+                        buckets[b].add(point[t], digit < 0);
+                }
+            }
+            integrate_range(p, &buckets[0], hi - lo, lo);
+            out.add(p);
+        }
+    };
+
+    if (nparts == 1)
+        run(0);
+    else
+        da_pool->par_map(nparts, run);
+
+    ret = parts[0];
+    for (size_t i = 1; i < nparts; i++)
+        ret.add(parts[i]);
+}
+
+#endif
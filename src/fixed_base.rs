@@ -0,0 +1,436 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::mem::size_of;
+use std::ops::Range;
+
+use halo2curves::ff::PrimeField;
+use halo2curves::group::prime::PrimeCurveAffine;
+use halo2curves::group::{Curve, Group};
+use rayon::prelude::*;
+
+use crate::config::{self, MsmConfig, Threads};
+use crate::pippenger::get_bits;
+use crate::{ffi, Msm, MsmError};
+
+/// Largest number of shifted copies of the generators kept by
+/// [`FixedBaseMsm::new`]. Every round streams the whole table through the
+/// caches, so past a few copies the rounds saved no longer make up for it.
+const DEFAULT_MAX_COPIES: usize = 4;
+
+/// Largest table built by [`FixedBaseMsm::new`], in bytes. Large keys get
+/// fewer copies, down to the generators alone.
+const DEFAULT_MAX_TABLE: usize = 64 << 20;
+
+/// Widest digit considered. The `2^(window-1)` buckets of a round take
+/// 1 MiB at this width, beyond which the bucket additions, which make up
+/// most of the work, start missing the L2 cache.
+const MAX_WINDOW: usize = 14;
+
+/// MSM context for a fixed set of generators, e.g. a commitment key.
+///
+/// Scalars are recoded into signed digits of `window` bits, and each
+/// generator `G` is stored alongside `2^(window * rounds * t) * G` for every
+/// copy `t`. An MSM then takes `rounds` bucket passes, each over all copies
+/// at once, where plain Pippenger takes one pass per window: the bucket sums
+/// are paid for once per round rather than once per window, and only half
+/// as many buckets are needed for the same window. With a copy for every
+/// digit there is a single round and no doubling at all.
+pub struct FixedBaseMsm<C: Msm> {
+    table: Vec<C>,
+    npoints: usize,
+    copies: usize,
+    window: usize,
+    rounds: usize,
+}
+
+impl<C: Msm> FixedBaseMsm<C> {
+    /// Precomputes up to 4 shifted copies of `points`, as many as pay off
+    /// for the number of generators, in a table of at most 64 MiB.
+    pub fn new(points: &[C]) -> Self {
+        let copies = default_copies::<C>(points.len());
+        let (window, rounds) = schedule::<C>(points.len(), copies);
+        Self::with_schedule(points, window, rounds)
+    }
+
+    /// Like [`FixedBaseMsm::new`], but fails with [`MsmError::InvalidPoint`]
+    /// if a point is not on the curve, see [`MsmConfig::validate`].
+    pub fn try_new(points: &[C]) -> Result<Self, MsmError> {
+        C::validate_points(points)?;
+        Ok(Self::new(points))
+    }
+
+    /// Precomputes as many shifted copies of `points` as pay off without
+    /// the table exceeding `budget` bytes. The table always holds at least
+    /// the generators themselves.
+    pub fn with_memory_budget(points: &[C], budget: usize) -> Self {
+        let copies = budget / (points.len().max(1) * size_of::<C>());
+        let (window, rounds) = schedule::<C>(points.len(), copies.max(1));
+        Self::with_schedule(points, window, rounds)
+    }
+
+    /// Like [`FixedBaseMsm::with_memory_budget`], but fails with
+    /// [`MsmError::InvalidPoint`] if a point is not on the curve.
+    pub fn try_with_memory_budget(
+        points: &[C],
+        budget: usize,
+    ) -> Result<Self, MsmError> {
+        C::validate_points(points)?;
+        Ok(Self::with_memory_budget(points, budget))
+    }
+
+    fn with_schedule(points: &[C], window: usize, rounds: usize) -> Self {
+        const CHUNK: usize = 1024;
+
+        let copies = copies::<C>(window, rounds);
+        let mut table = vec![C::identity(); points.len() * copies];
+        table
+            .par_chunks_mut(CHUNK * copies)
+            .zip(points.par_chunks(CHUNK))
+            .for_each(|(table, points)| {
+                let mut tmp = Vec::with_capacity(table.len());
+                for point in points {
+                    let mut point = point.to_curve();
+                    tmp.push(point);
+                    for _ in 1..copies {
+                        for _ in 0..window * rounds {
+                            point = point.double();
+                        }
+                        tmp.push(point);
+                    }
+                }
+                C::Curve::batch_normalize(&tmp, table);
+            });
+
+        Self {
+            table,
+            npoints: points.len(),
+            copies,
+            window,
+            rounds,
+        }
+    }
+
+    /// Number of generators.
+    pub fn len(&self) -> usize {
+        self.npoints
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.npoints == 0
+    }
+
+    /// Size of the precomputed table in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.table.len() * size_of::<C>()
+    }
+
+    /// Computes the MSM of the first `scalars.len()` generators with
+    /// `scalars`, which may be shorter than the generator set.
+    pub fn try_msm(&self, scalars: &[C::Scalar]) -> Result<C::Curve, MsmError> {
+        self.try_msm_with_config(scalars, &MsmConfig::default())
+    }
+
+    /// Like [`FixedBaseMsm::try_msm`], with the options of `config`. The
+    /// precomputed table is only used on the CPU, an MSM sent to the GPU
+    /// runs as a plain one over the generators. [`MsmConfig::validate`] has
+    /// no effect, the generators are checked by [`FixedBaseMsm::try_new`].
+    pub fn try_msm_with_config(
+        &self,
+        scalars: &[C::Scalar],
+        config: &MsmConfig,
+    ) -> Result<C::Curve, MsmError> {
+        if scalars.len() > self.npoints {
+            return Err(MsmError::LengthMismatch {
+                points: self.npoints,
+                scalars: scalars.len(),
+            });
+        }
+        if scalars.is_empty() {
+            return Ok(C::Curve::identity());
+        }
+        if config.use_gpu(scalars.len())? {
+            let points = self
+                .table
+                .iter()
+                .step_by(self.copies)
+                .take(scalars.len())
+                .copied()
+                .collect::<Vec<_>>();
+            return C::try_msm_with_config(&points, scalars, config);
+        }
+
+        let ndigits = self.copies * self.rounds;
+        let digits = recode(scalars, self.window, ndigits);
+        let msm = |range: Range<usize>, nthreads| {
+            ffi::mult_fixed_base(
+                &self.table[range.start * self.copies..range.end * self.copies],
+                self.copies,
+                &digits[range.start * ndigits..range.end * ndigits],
+                self.window,
+                self.rounds,
+                nthreads,
+            )
+        };
+        match config.threads {
+            Threads::Rayon => {
+                config::par_ranges(scalars.len(), |range| msm(range, 1))
+            }
+            threads => msm(0..scalars.len(), threads.nthreads()),
+        }
+    }
+
+    pub fn msm(&self, scalars: &[C::Scalar]) -> C::Curve {
+        self.try_msm(scalars).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+/// Number of signed digits of `window` bits needed for any scalar. The top
+/// digit may take a carry out of the one below it.
+fn ndigits<C: Msm>(window: usize) -> usize {
+    C::Scalar::NUM_BITS as usize / window + 1
+}
+
+/// Number of copies of each generator that `rounds` rounds of `window`-bit
+/// digits take.
+fn copies<C: Msm>(window: usize, rounds: usize) -> usize {
+    (ndigits::<C>(window) + rounds - 1) / rounds
+}
+
+/// Largest number of copies of `npoints` generators kept by
+/// [`FixedBaseMsm::new`].
+fn default_copies<C: Msm>(npoints: usize) -> usize {
+    let copies = DEFAULT_MAX_TABLE / (npoints.max(1) * size_of::<C>());
+    copies.clamp(1, DEFAULT_MAX_COPIES)
+}
+
+/// Picks the window and number of rounds, with at most `max_copies` copies
+/// of the generators, that minimize the estimated number of additions:
+/// one per digit and two per bucket in every round. A single copy amounts
+/// to plain Pippenger with signed digits, so the estimate never exceeds
+/// that of plain Pippenger.
+fn schedule<C: Msm>(npoints: usize, max_copies: usize) -> (usize, usize) {
+    (2..=MAX_WINDOW)
+        .map(|window| {
+            let ndigits = ndigits::<C>(window);
+            let rounds = (ndigits + max_copies - 1) / max_copies;
+            let copies = copies::<C>(window, rounds);
+            let cost = rounds * (npoints * copies + (1 << window));
+            (cost, window, rounds)
+        })
+        .min()
+        .map(|(_, window, rounds)| (window, rounds))
+        .unwrap()
+}
+
+/// Recodes every scalar into `ndigits` signed digits of `window` bits, each
+/// in `[-2^(window-1), 2^(window-1)]`, least significant first. A digit
+/// above half a window borrows from the next one.
+fn recode<F: PrimeField>(
+    scalars: &[F],
+    window: usize,
+    ndigits: usize,
+) -> Vec<i32> {
+    let half = 1 << (window - 1);
+    let mut digits = vec![0i32; scalars.len() * ndigits];
+    digits
+        .par_chunks_mut(ndigits)
+        .zip(scalars.par_iter())
+        .for_each(|(digits, scalar)| {
+            let repr = scalar.to_repr();
+            let mut carry = 0;
+            for (j, digit) in digits.iter_mut().enumerate() {
+                let value = get_bits(repr.as_ref(), j * window, window) as i32;
+                let value = value + carry;
+                carry = (value > half) as i32;
+                *digit = value - (carry << window);
+            }
+        });
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256;
+    use halo2curves::grumpkin;
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+    use crate::utils::{
+        gen_edge_scalars, gen_points_seeded, gen_scalars_seeded, off_curve,
+        test_seed, TEST_SEED_VAR,
+    };
+
+    fn check_fixed_base<C: Msm>() {
+        let seed = test_seed();
+        let points = gen_points_seeded::<C>(300, seed);
+        let scalars = gen_scalars_seeded::<C::Scalar>(300, seed);
+        let expected = C::msm(&points, &scalars);
+
+        for (window, rounds) in [(2, 1), (2, 40), (5, 3), (8, 33), (13, 2)] {
+            let msg = format!(
+                "window = {}, rounds = {}, {}={}",
+                window, rounds, TEST_SEED_VAR, seed
+            );
+            let ctx = FixedBaseMsm::with_schedule(&points, window, rounds);
+            let copies = copies::<C>(window, rounds);
+            assert_eq!(ctx.memory_usage(), 300 * copies * size_of::<C>());
+            assert_eq!(ctx.msm(&scalars), expected, "{}", msg);
+            let digits = recode(&scalars, window, copies * rounds);
+            let threaded = ffi::mult_fixed_base(
+                &ctx.table, copies, &digits, window, rounds, 4,
+            );
+            assert_eq!(threaded.unwrap(), expected, "{}", msg);
+            assert_eq!(
+                ctx.msm(&scalars[..37]),
+                C::msm(&points[..37], &scalars[..37]),
+                "{}",
+                msg
+            );
+            let edge = gen_edge_scalars::<C::Scalar>();
+            let edge = &edge[..edge.len().min(300)];
+            assert_eq!(
+                ctx.msm(edge),
+                C::msm(&points[..edge.len()], edge),
+                "{}",
+                msg
+            );
+        }
+
+        for budget in [0, 300 * size_of::<C>() * 3, usize::MAX] {
+            let ctx = FixedBaseMsm::with_memory_budget(&points, budget);
+            assert!(ctx.memory_usage() <= budget.max(300 * size_of::<C>()));
+            assert_eq!(
+                ctx.msm(&scalars),
+                expected,
+                "budget = {}, {}={}",
+                budget,
+                TEST_SEED_VAR,
+                seed
+            );
+        }
+        let ctx = FixedBaseMsm::new(&points);
+        assert!(
+            ctx.memory_usage() <= 300 * DEFAULT_MAX_COPIES * size_of::<C>()
+        );
+        for npow in [10, 16, 18, 20, 24] {
+            let npoints = 1 << npow;
+            let max_copies = default_copies::<C>(npoints);
+            let table = npoints * max_copies * size_of::<C>();
+            assert!(max_copies == 1 || table <= DEFAULT_MAX_TABLE);
+            let (window, rounds) = schedule::<C>(npoints, max_copies);
+            assert!(
+                window <= MAX_WINDOW
+                    && copies::<C>(window, rounds) <= max_copies
+            );
+        }
+        assert_eq!(ctx.msm(&scalars), expected, "{}={}", TEST_SEED_VAR, seed);
+
+        let mut bad = points.clone();
+        bad[123] = off_curve(bad[123]);
+        let invalid = Some(MsmError::InvalidPoint { index: 123 });
+        assert_eq!(FixedBaseMsm::try_new(&bad).err(), invalid);
+        assert_eq!(
+            FixedBaseMsm::try_with_memory_budget(&bad, usize::MAX).err(),
+            invalid
+        );
+        assert!(FixedBaseMsm::try_new(&points).is_ok());
+
+        let ctx = FixedBaseMsm::new(&points[..10]);
+        assert_eq!(
+            ctx.try_msm(&scalars[..11]),
+            Err(MsmError::LengthMismatch {
+                points: 10,
+                scalars: 11
+            })
+        );
+        assert_eq!(ctx.msm(&[]), C::Curve::identity());
+    }
+
+    fn check_fixed_base_small<C: Msm>() {
+        let seed = test_seed();
+        let points = gen_points_seeded::<C>(32, seed);
+        let scalars = gen_scalars_seeded::<C::Scalar>(32, seed);
+        for n in [1, 2, 3, 7, 8, 31, 32] {
+            let ctx = FixedBaseMsm::new(&points[..n]);
+            for len in [1, n] {
+                assert_eq!(
+                    ctx.msm(&scalars[..len]),
+                    C::msm(&points[..len], &scalars[..len]),
+                    "n = {}, len = {}, {}={}",
+                    n,
+                    len,
+                    TEST_SEED_VAR,
+                    seed
+                );
+            }
+        }
+    }
+
+    fn check_fixed_base_configs<C: Msm>() {
+        use crate::{gpu_available, Backend};
+
+        let seed = test_seed();
+        let points = gen_points_seeded::<C>(1100, seed);
+        let scalars = gen_scalars_seeded::<C::Scalar>(1100, seed);
+        let expected = C::msm(&points, &scalars);
+
+        let ctx = FixedBaseMsm::new(&points);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap();
+        for threads in [
+            Threads::Default,
+            Threads::Count(1),
+            Threads::Count(3),
+            Threads::Rayon,
+        ] {
+            let config = MsmConfig {
+                threads,
+                backend: Some(Backend::Cpu),
+                ..Default::default()
+            };
+            let msg = format!("{:?}, {}={}", threads, TEST_SEED_VAR, seed);
+            let msm = || ctx.try_msm_with_config(&scalars, &config);
+            assert_eq!(msm(), Ok(expected), "{}", msg);
+            assert_eq!(pool.install(msm), Ok(expected), "{}", msg);
+        }
+
+        let config = MsmConfig {
+            backend: Some(Backend::Gpu),
+            ..Default::default()
+        };
+        let gpu = ctx.try_msm_with_config(&scalars, &config);
+        if gpu_available() {
+            assert_eq!(gpu, Ok(expected), "{}={}", TEST_SEED_VAR, seed);
+        } else {
+            assert_eq!(gpu, Err(MsmError::GpuUnavailable));
+        }
+    }
+
+    #[test]
+    fn fixed_base_configs() {
+        check_fixed_base_configs::<bn256::G1Affine>();
+        check_fixed_base_configs::<grumpkin::G1Affine>();
+        check_fixed_base_configs::<pallas::Affine>();
+        check_fixed_base_configs::<vesta::Affine>();
+    }
+
+    #[test]
+    fn fixed_base_small() {
+        check_fixed_base_small::<bn256::G1Affine>();
+        check_fixed_base_small::<grumpkin::G1Affine>();
+        check_fixed_base_small::<pallas::Affine>();
+        check_fixed_base_small::<vesta::Affine>();
+    }
+
+    #[test]
+    fn fixed_base_matches_msm() {
+        check_fixed_base::<bn256::G1Affine>();
+        check_fixed_base::<grumpkin::G1Affine>();
+        check_fixed_base::<pallas::Affine>();
+        check_fixed_base::<vesta::Affine>();
+    }
+}
@@ -0,0 +1,409 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Binary fixtures of `(points, scalars, expected)` triples, for checking
+//! MSM implementations against each other.
+//!
+//! All integers are little-endian. A fixture file consists of
+//!
+//! | bytes       | content                                              |
+//! |-------------|------------------------------------------------------|
+//! | 4           | magic `MSMF`                                         |
+//! | 4           | format version, currently 1                          |
+//! | 4           | curve identifier, see [`CurveId`]                    |
+//! | 8           | number of points `n`                                 |
+//! | `n * 2 * B` | points as affine `x` and `y`, the identity as zeros  |
+//! | `n * S`     | scalars                                              |
+//! | `2 * B`     | expected MSM result, encoded like the points         |
+//! | 4           | CRC-32 (IEEE) of all preceding bytes                 |
+//!
+//! where field elements are canonical integers of `B` bytes for the base
+//! field and `S` bytes for the scalar field, i.e. 32 for all supported
+//! curves.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use halo2curves::ff::PrimeField;
+use halo2curves::CurveAffine;
+
+use crate::utils::naive_multiscalar_mul;
+use crate::{CurveId, Msm};
+
+/// First bytes of every fixture file.
+pub const MAGIC: [u8; 4] = *b"MSMF";
+
+/// Version of the format written by [`Fixture::to_bytes`].
+pub const VERSION: u32 = 1;
+
+const HEADER_LEN: usize = 20;
+
+/// Errors reported when decoding a fixture.
+#[derive(Debug)]
+pub enum FixtureError {
+    Io(io::Error),
+    /// The data does not start with [`MAGIC`].
+    BadMagic,
+    UnsupportedVersion(u32),
+    /// The fixture was recorded for another curve.
+    CurveMismatch {
+        expected: CurveId,
+        found: u32,
+    },
+    /// The data is not as long as its header implies.
+    Length {
+        expected: usize,
+        found: usize,
+    },
+    Checksum {
+        expected: u32,
+        found: u32,
+    },
+    /// The point at this index is not on the curve or not canonically
+    /// encoded. Index `n` refers to the expected result.
+    InvalidPoint(usize),
+    /// The scalar at this index is not canonically encoded.
+    InvalidScalar(usize),
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::BadMagic => write!(f, "not a fixture file"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported fixture version {}", version)
+            }
+            Self::CurveMismatch { expected, found } => write!(
+                f,
+                "fixture is for curve #{}, expected {}",
+                found, expected
+            ),
+            Self::Length { expected, found } => write!(
+                f,
+                "fixture is {} bytes long, expected {}",
+                found, expected
+            ),
+            Self::Checksum { expected, found } => write!(
+                f,
+                "checksum mismatch: {:#010x}, expected {:#010x}",
+                found, expected
+            ),
+            Self::InvalidPoint(index) => write!(f, "invalid point #{}", index),
+            Self::InvalidScalar(index) => {
+                write!(f, "invalid scalar #{}", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FixtureError {}
+
+impl From<io::Error> for FixtureError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Points and scalars of an MSM along with its result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fixture<C: CurveAffine> {
+    pub points: Vec<C>,
+    pub scalars: Vec<C::Scalar>,
+    pub expected: C,
+}
+
+impl<C: Msm> Fixture<C> {
+    /// Records `points` and `scalars` along with their MSM, computed one
+    /// scalar multiplication at a time. Panics if their lengths differ.
+    pub fn new(points: Vec<C>, scalars: Vec<C::Scalar>) -> Self {
+        let expected = naive_multiscalar_mul(&points, &scalars);
+        Self {
+            points,
+            scalars,
+            expected,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let n = self.points.len();
+        let mut out = Vec::with_capacity(encoded_len::<C>(n).unwrap());
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&(C::CURVE_ID as u32).to_le_bytes());
+        out.extend_from_slice(&(n as u64).to_le_bytes());
+        for point in &self.points {
+            write_point(&mut out, point);
+        }
+        for scalar in &self.scalars {
+            out.extend_from_slice(scalar.to_repr().as_ref());
+        }
+        write_point(&mut out, &self.expected);
+        let checksum = crc32(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FixtureError> {
+        if bytes.len() < HEADER_LEN || bytes[..4] != MAGIC {
+            return Err(FixtureError::BadMagic);
+        }
+        let version = read_u32(&bytes[4..]);
+        if version != VERSION {
+            return Err(FixtureError::UnsupportedVersion(version));
+        }
+        let curve = read_u32(&bytes[8..]);
+        if curve != C::CURVE_ID as u32 {
+            return Err(FixtureError::CurveMismatch {
+                expected: C::CURVE_ID,
+                found: curve,
+            });
+        }
+        let n = u64::from_le_bytes(bytes[12..20].try_into().unwrap());
+        let expected_len = usize::try_from(n)
+            .ok()
+            .and_then(encoded_len::<C>)
+            .unwrap_or(usize::MAX);
+        if bytes.len() != expected_len {
+            return Err(FixtureError::Length {
+                expected: expected_len,
+                found: bytes.len(),
+            });
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - 4);
+        let (expected, found) = (read_u32(checksum), crc32(body));
+        if expected != found {
+            return Err(FixtureError::Checksum { expected, found });
+        }
+
+        let n = n as usize;
+        let point_len = 2 * base_len::<C>();
+        let scalar_len = scalar_len::<C>();
+        let (points, rest) = body[HEADER_LEN..].split_at(n * point_len);
+        let (scalars, result) = rest.split_at(n * scalar_len);
+
+        let points = points
+            .chunks(point_len)
+            .enumerate()
+            .map(|(i, bytes)| {
+                read_point(bytes).ok_or(FixtureError::InvalidPoint(i))
+            })
+            .collect::<Result<Vec<C>, _>>()?;
+        let scalars = scalars
+            .chunks(scalar_len)
+            .enumerate()
+            .map(|(i, bytes)| {
+                let mut repr = <C::Scalar as PrimeField>::Repr::default();
+                repr.as_mut().copy_from_slice(bytes);
+                Option::from(C::Scalar::from_repr(repr))
+                    .ok_or(FixtureError::InvalidScalar(i))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let expected =
+            read_point(result).ok_or(FixtureError::InvalidPoint(n))?;
+
+        Ok(Self {
+            points,
+            scalars,
+            expected,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, FixtureError> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, FixtureError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+
+fn base_len<C: CurveAffine>() -> usize {
+    <C::Base as PrimeField>::Repr::default().as_ref().len()
+}
+
+fn scalar_len<C: CurveAffine>() -> usize {
+    <C::Scalar as PrimeField>::Repr::default().as_ref().len()
+}
+
+fn encoded_len<C: CurveAffine>(n: usize) -> Option<usize> {
+    let per_point = 2 * base_len::<C>() + scalar_len::<C>();
+    n.checked_mul(per_point)?
+        .checked_add(HEADER_LEN + 2 * base_len::<C>() + 4)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
+
+fn write_point<C: CurveAffine>(out: &mut Vec<u8>, point: &C) {
+    let coordinates = point.coordinates();
+    if bool::from(coordinates.is_some()) {
+        let coordinates = coordinates.unwrap();
+        out.extend_from_slice(coordinates.x().to_repr().as_ref());
+        out.extend_from_slice(coordinates.y().to_repr().as_ref());
+    } else {
+        out.resize(out.len() + 2 * base_len::<C>(), 0);
+    }
+}
+
+fn read_point<C: CurveAffine>(bytes: &[u8]) -> Option<C> {
+    if bytes.iter().all(|&byte| byte == 0) {
+        return Some(C::identity());
+    }
+    let (x, y) = bytes.split_at(bytes.len() / 2);
+    let read = |bytes: &[u8]| {
+        let mut repr = <C::Base as PrimeField>::Repr::default();
+        repr.as_mut().copy_from_slice(bytes);
+        Option::<C::Base>::from(C::Base::from_repr(repr))
+    };
+    Option::from(C::from_xy(read(x)?, read(y)?))
+}
+
+/// CRC-32 with the IEEE polynomial, as used by zlib and Go's `hash/crc32`.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut k = 0;
+            while k < 8 {
+                crc = if crc & 1 != 0 {
+                    0xedb8_8320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+                k += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use halo2curves::ff::Field;
+    use halo2curves::group::Curve;
+    use halo2curves::{bn256, grumpkin};
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+    use crate::utils::{gen_points_seeded, gen_scalars_seeded};
+
+    fn fixture_path(curve: CurveId) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(format!("{}.bin", curve))
+    }
+
+    fn check_fixture<C: Msm>(msm: fn(&[C], &[C::Scalar]) -> C::Curve) {
+        let fixture = Fixture::<C>::load(fixture_path(C::CURVE_ID)).unwrap();
+        assert_eq!(
+            msm(&fixture.points, &fixture.scalars).to_affine(),
+            fixture.expected
+        );
+        assert_eq!(Fixture::from_bytes(&fixture.to_bytes()).unwrap(), fixture);
+    }
+
+    #[test]
+    fn fixtures() {
+        check_fixture(crate::bn256);
+        check_fixture(crate::grumpkin);
+        check_fixture(crate::pasta::pallas);
+        check_fixture(crate::pasta::vesta);
+    }
+
+    #[test]
+    fn rejects_corrupted_fixtures() {
+        let fixture = Fixture::<bn256::G1Affine>::new(
+            gen_points_seeded(10, 1),
+            gen_scalars_seeded(10, 1),
+        );
+        let bytes = fixture.to_bytes();
+
+        let mut corrupted = bytes.clone();
+        corrupted[100] ^= 1;
+        assert!(matches!(
+            Fixture::<bn256::G1Affine>::from_bytes(&corrupted),
+            Err(FixtureError::Checksum { .. })
+        ));
+        assert!(matches!(
+            Fixture::<bn256::G1Affine>::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(FixtureError::Length { .. })
+        ));
+        assert!(matches!(
+            Fixture::<grumpkin::G1Affine>::from_bytes(&bytes),
+            Err(FixtureError::CurveMismatch {
+                expected: CurveId::Grumpkin,
+                found: 1
+            })
+        ));
+
+        let mut corrupted = bytes.clone();
+        corrupted[4] = 2;
+        assert!(matches!(
+            Fixture::<bn256::G1Affine>::from_bytes(&corrupted),
+            Err(FixtureError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            Fixture::<bn256::G1Affine>::from_bytes(b"not a fixture"),
+            Err(FixtureError::BadMagic)
+        ));
+
+        // Valid checksum, but a y coordinate that is off the curve.
+        let mut corrupted = bytes[..bytes.len() - 4].to_vec();
+        corrupted[HEADER_LEN + 64 + 32] ^= 1;
+        let checksum = crc32(&corrupted);
+        corrupted.extend_from_slice(&checksum.to_le_bytes());
+        assert!(matches!(
+            Fixture::<bn256::G1Affine>::from_bytes(&corrupted),
+            Err(FixtureError::InvalidPoint(1))
+        ));
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    fn write_fixture<C: Msm>() {
+        let mut scalars = gen_scalars_seeded::<C::Scalar>(100, 2);
+        scalars[0] = C::Scalar::ZERO;
+        scalars[1] = C::Scalar::ONE;
+        scalars[2] = -C::Scalar::ONE;
+        let fixture = Fixture::<C>::new(gen_points_seeded(100, 1), scalars);
+        fixture.save(fixture_path(C::CURVE_ID)).unwrap();
+    }
+
+    /// Rewrites the checked-in fixtures, run with `--ignored`.
+    #[test]
+    #[ignore]
+    fn regenerate_fixtures() {
+        write_fixture::<bn256::G1Affine>();
+        write_fixture::<grumpkin::G1Affine>();
+        write_fixture::<pallas::Affine>();
+        write_fixture::<vesta::Affine>();
+    }
+}
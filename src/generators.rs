@@ -0,0 +1,101 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use halo2curves::group::Curve;
+use halo2curves::{CurveAffine, CurveExt};
+use rayon::prelude::*;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::Shake256;
+
+/// Domain of the hash to curve applied to every 32-byte block.
+const DOMAIN: &str = "from_uniform_bytes";
+
+/// Derives `n` generators from `label`, the same way as `from_label` in
+/// Nova: the label is expanded with SHAKE256 into 32 bytes per generator,
+/// each of which is hashed to the curve in the `"from_uniform_bytes"`
+/// domain. Nobody knows discrete logarithms between the results, and a
+/// shorter derivation from the same label is a prefix of a longer one.
+pub fn generators<C: CurveAffine>(label: &[u8], n: usize) -> Vec<C> {
+    // Blocks squeezed at a time, hashed to the curve in parallel in chunks
+    // of CHUNK with one inversion each.
+    const BATCH: usize = 1 << 16;
+    const CHUNK: usize = 1024;
+
+    let mut shake = Shake256::default();
+    shake.update(label);
+    let mut reader = shake.finalize_xof();
+    let mut bytes = vec![0u8; 32 * BATCH.min(n)];
+    let mut ret = vec![C::identity(); n];
+    for ret in ret.chunks_mut(BATCH) {
+        let bytes = &mut bytes[..32 * ret.len()];
+        reader.read(bytes);
+        ret.par_chunks_mut(CHUNK)
+            .zip(bytes.par_chunks(32 * CHUNK))
+            .for_each(|(ret, bytes)| {
+                let hash = C::CurveExt::hash_to_curve(DOMAIN);
+                let tmp = bytes.chunks(32).map(&hash).collect::<Vec<_>>();
+                C::CurveExt::batch_normalize(&tmp, ret);
+            });
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256;
+    use halo2curves::group::GroupEncoding;
+    use halo2curves::grumpkin;
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+
+    /// First 96 bytes of SHAKE256("test"), computed independently.
+    const TEST_BYTES: &str = "b54ff7255705a71ee2925e4a3e30e41aed489a579d5595e0\
+        df13e32e1e4dd202a7c7f68b31d6418d9845eb4d757adda6ab189e1bb340db818e5b\
+        3bc725d992faf63e9b0500db10517fe09d3f566fba3a80e46a403e0c7d41548fbf75\
+        cf2662b0";
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn check_generators<C: CurveAffine>(expected: &str) {
+        let gens = generators::<C>(b"test", 2000);
+        assert_eq!(gens[..3], generators::<C>(b"test", 3)[..]);
+        assert_ne!(gens[..3], generators::<C>(b"tess", 3)[..]);
+        assert!(generators::<C>(b"test", 0).is_empty());
+        assert!(gens
+            .iter()
+            .all(|p| bool::from(p.is_on_curve()) && p != &C::identity()));
+
+        let hash = C::CurveExt::hash_to_curve(DOMAIN);
+        for (gen, bytes) in gens.iter().zip(unhex(TEST_BYTES).chunks(32)) {
+            assert_eq!(*gen, hash(bytes).to_affine());
+        }
+
+        // Compressed encoding of the first generator.
+        let encoded = gens[0].to_bytes();
+        assert_eq!(unhex(expected), encoded.as_ref());
+    }
+
+    #[test]
+    fn known_answers() {
+        check_generators::<bn256::G1Affine>(
+            "857732a5b069cf23f17c46189ef75b3067735a0a58509433fcd6d43fddf06444",
+        );
+        check_generators::<grumpkin::G1Affine>(
+            "a20858a61b3fa062b09a8626d472e59dcad18963e75e61ee86e143dec1a1af55",
+        );
+        check_generators::<pallas::Affine>(
+            "8b9022bed47e3abda609f39688e1d406d16eb6650c95113d56d051c832e8b19f",
+        );
+        check_generators::<vesta::Affine>(
+            "b5bde9b0b2950ebdb2642d08e3147538d48734d96392772de5b677429fd40e2d",
+        );
+    }
+}
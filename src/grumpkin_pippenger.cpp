@@ -7,20 +7,89 @@
 #include <ec/xyzz_t.hpp>
 #include <ff/alt_bn128.hpp>
 
+#include "fixed_base.hpp"
+#include "pippenger_batch.hpp"
+#include "pippenger_bits.hpp"
+#include "thread_pools.hpp"
+
+using namespace alt_bn128;
+
 static thread_pool_t da_pool;
 
 extern "C"
 void mult_pippenger_bn254(jacobian_t<fp_t>& ret,
                           const xyzz_t<fp_t>::affine_t points[],
-                          size_t npoints, const fr_t scalars[])
-{   mult_pippenger<xyzz_t<fp_t>>(ret, points, npoints, scalars, true,
-                                    &da_pool);
+                          size_t npoints, const fr_t scalars[], bool mont,
+                          size_t nthreads)
+{   mult_pippenger<xyzz_t<fp_t>>(ret, points, npoints, scalars, mont,
+                                 select_pool(da_pool, nthreads));
 }
 
 extern "C"
 void mult_pippenger_grumpkin(jacobian_t<fr_t>& ret,
                            const xyzz_t<fr_t>::affine_t points[],
-                           size_t npoints, const fp_t scalars[])
-{   mult_pippenger<xyzz_t<fr_t>>(ret, points, npoints, scalars, true,
-                                     &da_pool);
-}
\ No newline at end of file
+                           size_t npoints, const fp_t scalars[], bool mont,
+                           size_t nthreads)
+{   mult_pippenger<xyzz_t<fr_t>>(ret, points, npoints, scalars, mont,
+                                 select_pool(da_pool, nthreads));
+}
+
+extern "C"
+void mult_pippenger_bits_bn254(jacobian_t<fp_t>& ret,
+                               const xyzz_t<fp_t>::affine_t points[],
+                               size_t npoints, const unsigned char scalars[],
+                               size_t nbits, size_t nthreads)
+{   mult_pippenger_bits<xyzz_t<fp_t>>(ret, points, npoints, scalars, nbits,
+                                      select_pool(da_pool, nthreads));
+}
+
+extern "C"
+void mult_pippenger_bits_grumpkin(jacobian_t<fr_t>& ret,
+                                  const xyzz_t<fr_t>::affine_t points[],
+                                  size_t npoints, const unsigned char scalars[],
+                                  size_t nbits, size_t nthreads)
+{   mult_pippenger_bits<xyzz_t<fr_t>>(ret, points, npoints, scalars, nbits,
+                                      select_pool(da_pool, nthreads));
+}
+
+extern "C"
+void mult_pippenger_batch_bn254(jacobian_t<fp_t> ret[],
+                                const xyzz_t<fp_t>::affine_t points[],
+                                size_t npoints, const fr_t* const scalars[],
+                                size_t nbatches, size_t nthreads)
+{   mult_pippenger_batch<xyzz_t<fp_t>, fp_t>(ret, points, npoints,
+                                             scalars, nbatches,
+                                             select_pool(da_pool, nthreads));
+}
+
+extern "C"
+void mult_pippenger_batch_grumpkin(jacobian_t<fr_t> ret[],
+                                   const xyzz_t<fr_t>::affine_t points[],
+                                   size_t npoints, const fp_t* const scalars[],
+                                   size_t nbatches, size_t nthreads)
+{   mult_pippenger_batch<xyzz_t<fr_t>, fr_t>(ret, points, npoints,
+                                             scalars, nbatches,
+                                             select_pool(da_pool, nthreads));
+}
+
+extern "C"
+void mult_fixed_base_bn254(jacobian_t<fp_t>& ret,
+                           const xyzz_t<fp_t>::affine_t table[],
+                           size_t npoints, size_t ncopies,
+                           const int digits[], size_t window,
+                           size_t nrounds, size_t nthreads)
+{   mult_fixed_base<xyzz_t<fp_t>>(ret, table, npoints, ncopies,
+                                  digits, window, nrounds,
+                                  select_pool(da_pool, nthreads));
+}
+
+extern "C"
+void mult_fixed_base_grumpkin(jacobian_t<fr_t>& ret,
+                              const xyzz_t<fr_t>::affine_t table[],
+                              size_t npoints, size_t ncopies,
+                              const int digits[], size_t window,
+                              size_t nrounds, size_t nthreads)
+{   mult_fixed_base<xyzz_t<fr_t>>(ret, table, npoints, ncopies,
+                                  digits, window, nrounds,
+                                  select_pool(da_pool, nthreads));
+}
@@ -0,0 +1,595 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Commitment key files: arrays of affine points stored in their in-memory
+//! representation, so that they can be memory-mapped and handed to the MSM
+//! entry points as they are.
+//!
+//! All header integers are little-endian. A key file consists of
+//!
+//! | bytes   | content                                                |
+//! |---------|--------------------------------------------------------|
+//! | 4       | magic `MSMK`                                           |
+//! | 4       | format version, currently 1                            |
+//! | 4       | curve identifier, see [`CurveId`]                      |
+//! | 4       | size of a point in bytes, `P`                          |
+//! | 8       | number of points `n`                                   |
+//! | 4       | layout hash, see [`layout_hash`]                       |
+//! | 4       | CRC-32 (IEEE) of the preceding 28 bytes                |
+//! | 32      | zeros                                                  |
+//! | `n * P` | points, as laid out in memory                          |
+//!
+//! The header is padded to 64 bytes to keep the points aligned within a
+//! mapping. Only the header is checksummed; the points are checked when the
+//! file is written, but not when it is opened.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::mem::size_of;
+use std::ops::Deref;
+use std::path::Path;
+use std::slice;
+
+use halo2curves::group::prime::PrimeCurveAffine;
+
+use crate::fixture::crc32;
+use crate::{CurveId, Msm};
+
+/// First bytes of every key file.
+pub const MAGIC: [u8; 4] = *b"MSMK";
+
+/// Version of the format written by [`KeyFile::write`].
+pub const VERSION: u32 = 1;
+
+const HEADER_LEN: usize = 64;
+const CHECKED_LEN: usize = 28;
+
+/// Errors reported when reading or writing a key file.
+#[derive(Debug)]
+pub enum KeyError {
+    Io(io::Error),
+    /// The data does not start with [`MAGIC`].
+    BadMagic,
+    UnsupportedVersion(u32),
+    /// The header checksum does not match.
+    Checksum {
+        expected: u32,
+        found: u32,
+    },
+    /// The key was written for another curve.
+    CurveMismatch {
+        expected: CurveId,
+        found: u32,
+    },
+    /// The key was written by a build that lays out points differently,
+    /// e.g. with another point size, field encoding or byte order.
+    LayoutMismatch {
+        expected: u32,
+        found: u32,
+    },
+    /// The data is not as long as its header implies.
+    Length {
+        expected: u64,
+        found: u64,
+    },
+    /// The point at this index is not on the curve.
+    InvalidPoint(usize),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::BadMagic => write!(f, "not a key file"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported key file version {}", version)
+            }
+            Self::Checksum { expected, found } => write!(
+                f,
+                "header checksum mismatch: {:#010x}, expected {:#010x}",
+                found, expected
+            ),
+            Self::CurveMismatch { expected, found } => {
+                write!(f, "key is for curve #{}, expected {}", found, expected)
+            }
+            Self::LayoutMismatch { expected, found } => write!(
+                f,
+                "key layout {:#010x} does not match {:#010x}",
+                found, expected
+            ),
+            Self::Length { expected, found } => write!(
+                f,
+                "key file is {} bytes long, expected {}",
+                found, expected
+            ),
+            Self::InvalidPoint(index) => write!(f, "invalid point #{}", index),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+impl From<io::Error> for KeyError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// CRC-32 of the in-memory representation of the generator and its
+/// negation, along with the point size. It changes with anything that
+/// makes raw points unportable between builds, such as the field element
+/// encoding, limb order, byte order or struct layout.
+pub fn layout_hash<C: Msm>() -> u32 {
+    let points = [C::generator(), -C::generator()];
+    let mut bytes = (size_of::<C>() as u32).to_le_bytes().to_vec();
+    bytes.extend_from_slice(as_bytes(&points));
+    crc32(&bytes)
+}
+
+/// Points of a key file, either memory-mapped or read into memory.
+/// Dereferences to `[C]`, to be passed to the MSM entry points.
+pub struct KeyFile<C: Msm> {
+    storage: Storage<C>,
+}
+
+enum Storage<C> {
+    #[cfg(unix)]
+    Mapped(mmap::Mmap),
+    Owned(Vec<C>),
+}
+
+impl<C: Msm> KeyFile<C> {
+    /// Reads the key file at `path` into memory. The header is checked, but
+    /// the points are not, see [`KeyFile::read`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, KeyError> {
+        let mut file = File::open(path)?;
+        let mut header = [0u8; HEADER_LEN];
+        read_header(&mut file, &mut header)?;
+        let n = parse_header::<C>(&header)?;
+        // Saves allocating for a file shorter than its header claims.
+        check_len::<C>(n, file.metadata()?.len())?;
+        Self::read_points(file, n)
+    }
+
+    /// Like [`KeyFile::open`], but memory-maps the file on Unix rather than
+    /// reading it, which it still does elsewhere.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or any other
+    /// process, for as long as the returned key is alive. The points are
+    /// read straight from the mapping, so a write shows through to them and
+    /// a truncation makes accessing them fault.
+    pub unsafe fn map<P: AsRef<Path>>(path: P) -> Result<Self, KeyError> {
+        #[cfg(unix)]
+        return Self::map_file(File::open(path)?);
+        #[cfg(not(unix))]
+        return Self::open(path);
+    }
+
+    #[cfg(unix)]
+    unsafe fn map_file(mut file: File) -> Result<Self, KeyError> {
+        let mut header = [0u8; HEADER_LEN];
+        read_header(&mut file, &mut header)?;
+        let n = parse_header::<C>(&header)?;
+        check_len::<C>(n, file.metadata()?.len())?;
+
+        let map = mmap::Mmap::map(&file, HEADER_LEN + n * point_len::<C>())?;
+        Ok(Self {
+            storage: Storage::Mapped(map),
+        })
+    }
+
+    /// Reads a key into memory from `reader`. As with [`KeyFile::open`],
+    /// the points are not checked: the header rules out a key for another
+    /// curve or layout, but not one with non-canonical coordinates or
+    /// points off the curve, for which MSMs silently return garbage. Such
+    /// points are caught by [`Msm::validate_points`], by
+    /// [`MsmConfig::validate`](crate::MsmConfig::validate) and by `try_new`
+    /// constructors such as
+    /// [`CommitmentKey::try_new`](crate::pedersen::CommitmentKey::try_new).
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, KeyError> {
+        let mut header = [0u8; HEADER_LEN];
+        read_header(&mut reader, &mut header)?;
+        let n = parse_header::<C>(&header)?;
+        Self::read_points(reader, n)
+    }
+
+    /// Reads the `n` points following the header straight into the key, so
+    /// that no more than the key itself is ever held in memory.
+    fn read_points<R: Read>(mut reader: R, n: usize) -> Result<Self, KeyError> {
+        let mut points = vec![];
+        points
+            .try_reserve_exact(n)
+            .map_err(|_| io::Error::from(io::ErrorKind::OutOfMemory))?;
+        points.resize(n, C::identity());
+        // Every bit pattern is a value of the field element limbs, so this
+        // is sound, but that is all it is: nothing here checks that the
+        // coordinates are canonical or the points on the curve.
+        let bytes = unsafe {
+            slice::from_raw_parts_mut(
+                points.as_mut_ptr() as *mut u8,
+                n * point_len::<C>(),
+            )
+        };
+        let len = read_full(&mut reader, bytes)?;
+        let trailing = io::copy(&mut reader, &mut io::sink())?;
+        check_len::<C>(n, (HEADER_LEN + len) as u64 + trailing)?;
+        Ok(Self {
+            storage: Storage::Owned(points),
+        })
+    }
+
+    /// Writes `points` in the key file format, after checking that they
+    /// are all on the curve.
+    pub fn write<W: Write>(
+        mut writer: W,
+        points: &[C],
+    ) -> Result<(), KeyError> {
+        C::validate_points(points).map_err(|err| match err {
+            crate::MsmError::InvalidPoint { index } => {
+                KeyError::InvalidPoint(index)
+            }
+            err => unreachable!("{}", err),
+        })?;
+
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&(C::CURVE_ID as u32).to_le_bytes());
+        header.extend_from_slice(&(point_len::<C>() as u32).to_le_bytes());
+        header.extend_from_slice(&(points.len() as u64).to_le_bytes());
+        header.extend_from_slice(&layout_hash::<C>().to_le_bytes());
+        let checksum = crc32(&header);
+        header.extend_from_slice(&checksum.to_le_bytes());
+        header.resize(HEADER_LEN, 0);
+
+        writer.write_all(&header)?;
+        writer.write_all(as_bytes(points))?;
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(path: P, points: &[C]) -> Result<(), KeyError> {
+        let mut file = io::BufWriter::new(File::create(path)?);
+        Self::write(&mut file, points)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn points(&self) -> &[C] {
+        match &self.storage {
+            #[cfg(unix)]
+            Storage::Mapped(map) => unsafe {
+                slice::from_raw_parts(
+                    map.as_ptr().add(HEADER_LEN) as *const C,
+                    (map.len() - HEADER_LEN) / point_len::<C>(),
+                )
+            },
+            Storage::Owned(points) => points,
+        }
+    }
+
+    /// Whether the points are memory-mapped rather than read into memory.
+    pub fn is_mapped(&self) -> bool {
+        match self.storage {
+            #[cfg(unix)]
+            Storage::Mapped(_) => true,
+            Storage::Owned(_) => false,
+        }
+    }
+}
+
+impl<C: Msm> Deref for KeyFile<C> {
+    type Target = [C];
+
+    fn deref(&self) -> &[C] {
+        self.points()
+    }
+}
+
+fn point_len<C: Msm>() -> usize {
+    size_of::<C>()
+}
+
+/// In-memory representation of `points`. Affine points of all supported
+/// curves are two field elements of `u64` limbs, without padding.
+fn as_bytes<C: Msm>(points: &[C]) -> &[u8] {
+    unsafe {
+        slice::from_raw_parts(
+            points.as_ptr() as *const u8,
+            points.len() * point_len::<C>(),
+        )
+    }
+}
+
+/// Reads from `reader` until `buf` is full or the input ends, returning the
+/// number of bytes read.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(len)
+}
+
+fn read_header<R: Read>(
+    reader: &mut R,
+    header: &mut [u8; HEADER_LEN],
+) -> Result<(), KeyError> {
+    reader.read_exact(header).map_err(|err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => KeyError::BadMagic,
+        _ => err.into(),
+    })
+}
+
+/// Checks the header and returns the number of points.
+fn parse_header<C: Msm>(header: &[u8; HEADER_LEN]) -> Result<usize, KeyError> {
+    let read_u32 =
+        |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+
+    if header[..4] != MAGIC {
+        return Err(KeyError::BadMagic);
+    }
+    let version = read_u32(4);
+    if version != VERSION {
+        return Err(KeyError::UnsupportedVersion(version));
+    }
+    let (expected, found) =
+        (read_u32(CHECKED_LEN), crc32(&header[..CHECKED_LEN]));
+    if expected != found {
+        return Err(KeyError::Checksum { expected, found });
+    }
+    let curve = read_u32(8);
+    if curve != C::CURVE_ID as u32 {
+        return Err(KeyError::CurveMismatch {
+            expected: C::CURVE_ID,
+            found: curve,
+        });
+    }
+    let (expected, found) = (layout_hash::<C>(), read_u32(24));
+    if read_u32(12) as usize != point_len::<C>() || expected != found {
+        return Err(KeyError::LayoutMismatch { expected, found });
+    }
+
+    let n = u64::from_le_bytes(header[16..24].try_into().unwrap());
+    usize::try_from(n)
+        .ok()
+        .filter(|n| {
+            n.checked_mul(point_len::<C>())
+                .and_then(|len| len.checked_add(HEADER_LEN))
+                .is_some()
+        })
+        .ok_or(KeyError::Length {
+            expected: u64::MAX,
+            found: n,
+        })
+}
+
+fn check_len<C: Msm>(n: usize, found: u64) -> Result<(), KeyError> {
+    let expected = (HEADER_LEN + n * point_len::<C>()) as u64;
+    if expected != found {
+        return Err(KeyError::Length { expected, found });
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+mod mmap {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std::ptr;
+
+    /// Read-only private mapping of a whole file.
+    pub(super) struct Mmap {
+        ptr: *mut libc::c_void,
+        len: usize,
+    }
+
+    // The mapping is never written to.
+    unsafe impl Send for Mmap {}
+    unsafe impl Sync for Mmap {}
+
+    impl Mmap {
+        /// # Safety
+        ///
+        /// The file must not be modified or truncated while mapped.
+        pub(super) unsafe fn map(file: &File, len: usize) -> io::Result<Self> {
+            let ptr = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    len,
+                    libc::PROT_READ,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { ptr, len })
+        }
+
+        pub(super) fn as_ptr(&self) -> *const u8 {
+            self.ptr as *const u8
+        }
+
+        pub(super) fn len(&self) -> usize {
+            self.len
+        }
+    }
+
+    impl Drop for Mmap {
+        fn drop(&mut self) {
+            unsafe { libc::munmap(self.ptr, self.len) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use halo2curves::bn256;
+    use halo2curves::group::Group;
+    use halo2curves::grumpkin;
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+    use crate::utils::{gen_points_seeded, gen_scalars_seeded, off_curve};
+
+    fn key_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "grumpkin-msm-{}-{}.key",
+            std::process::id(),
+            name
+        ))
+    }
+
+    fn check_key_file<C: Msm>() {
+        let mut points = gen_points_seeded::<C>(1000, 22);
+        points[5] = C::identity();
+        let scalars = gen_scalars_seeded::<C::Scalar>(1000, 22);
+        let path = key_path(C::CURVE_ID.name());
+
+        KeyFile::save(&path, &points).unwrap();
+        let key = KeyFile::<C>::open(&path).unwrap();
+        assert!(!key.is_mapped());
+        assert_eq!(&key[..], &points[..]);
+        assert_eq!(C::msm(&key, &scalars), C::msm(&points, &scalars));
+        // The file is neither modified nor removed until the key is dropped.
+        let key = unsafe { KeyFile::<C>::map(&path) }.unwrap();
+        assert_eq!(key.is_mapped(), cfg!(unix));
+        assert_eq!(&key[..], &points[..]);
+        assert_eq!(C::msm(&key, &scalars), C::msm(&points, &scalars));
+        drop(key);
+
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), HEADER_LEN + 1000 * size_of::<C>());
+        let key = KeyFile::<C>::read(&bytes[..]).unwrap();
+        assert!(!key.is_mapped());
+        assert_eq!(&key[..], &points[..]);
+        // Points arriving in pieces, split within a point.
+        let (head, tail) = bytes.split_at(HEADER_LEN + 100);
+        let key = KeyFile::<C>::read(head.chain(tail)).unwrap();
+        assert_eq!(&key[..], &points[..]);
+
+        KeyFile::<C>::save(&path, &[]).unwrap();
+        let key = KeyFile::<C>::open(&path).unwrap();
+        assert!(key.is_empty());
+        assert_eq!(C::msm(&key, &[]), C::Curve::identity());
+        let key = unsafe { KeyFile::<C>::map(&path) }.unwrap();
+        assert!(key.is_empty());
+        drop(key);
+        fs::remove_file(&path).unwrap();
+
+        points[7] = off_curve(points[7]);
+        assert!(matches!(
+            KeyFile::write(&mut vec![], &points),
+            Err(KeyError::InvalidPoint(7))
+        ));
+    }
+
+    #[test]
+    fn key_files() {
+        check_key_file::<bn256::G1Affine>();
+        check_key_file::<grumpkin::G1Affine>();
+        check_key_file::<pallas::Affine>();
+        check_key_file::<vesta::Affine>();
+    }
+
+    #[test]
+    fn rejects_corrupted_keys() {
+        let points = gen_points_seeded::<bn256::G1Affine>(10, 22);
+        let mut bytes = vec![];
+        KeyFile::write(&mut bytes, &points).unwrap();
+        let read = |bytes: &[u8]| KeyFile::<bn256::G1Affine>::read(bytes);
+
+        // Rewrites a header field along with the checksum.
+        let patch = |at: usize, value: &[u8]| {
+            let mut bytes = bytes.clone();
+            bytes[at..at + value.len()].copy_from_slice(value);
+            let checksum = crc32(&bytes[..CHECKED_LEN]);
+            bytes[CHECKED_LEN..CHECKED_LEN + 4]
+                .copy_from_slice(&checksum.to_le_bytes());
+            bytes
+        };
+
+        assert!(matches!(read(b"not a key file"), Err(KeyError::BadMagic)));
+        assert!(matches!(
+            read(&patch(4, &[2])),
+            Err(KeyError::UnsupportedVersion(2))
+        ));
+        let mut corrupted = bytes.clone();
+        corrupted[16] ^= 1;
+        assert!(matches!(read(&corrupted), Err(KeyError::Checksum { .. })));
+        assert!(matches!(
+            KeyFile::<grumpkin::G1Affine>::read(&bytes[..]),
+            Err(KeyError::CurveMismatch {
+                expected: CurveId::Grumpkin,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            read(&patch(24, &[0; 4])),
+            Err(KeyError::LayoutMismatch { found: 0, .. })
+        ));
+        assert!(matches!(
+            read(&patch(12, &[96])),
+            Err(KeyError::LayoutMismatch { .. })
+        ));
+        assert!(matches!(
+            read(&bytes[..bytes.len() - 1]),
+            Err(KeyError::Length {
+                expected: 704,
+                found: 703
+            })
+        ));
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(matches!(
+            read(&longer),
+            Err(KeyError::Length {
+                expected: 704,
+                found: 705
+            })
+        ));
+        assert!(matches!(
+            read(&patch(16, &u64::MAX.to_le_bytes())),
+            Err(KeyError::Length { .. })
+        ));
+
+        let path = key_path("truncated");
+        fs::write(&path, &bytes[..bytes.len() - 64]).unwrap();
+        assert!(matches!(
+            KeyFile::<bn256::G1Affine>::open(&path),
+            Err(KeyError::Length { .. })
+        ));
+        assert!(matches!(
+            unsafe { KeyFile::<bn256::G1Affine>::map(&path) },
+            Err(KeyError::Length { .. })
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn layout_hashes_differ() {
+        let hashes = [
+            layout_hash::<bn256::G1Affine>(),
+            layout_hash::<grumpkin::G1Affine>(),
+            layout_hash::<pallas::Affine>(),
+            layout_hash::<vesta::Affine>(),
+        ];
+        for (i, a) in hashes.iter().enumerate() {
+            assert!(hashes[i + 1..].iter().all(|b| a != b));
+        }
+    }
+}
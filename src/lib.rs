@@ -4,9 +4,47 @@
 #![allow(improper_ctypes)]
 #![allow(unused)]
 
+mod accumulator;
+mod canonical;
+mod claims;
+mod config;
+mod curve;
+mod error;
+mod ffi;
+mod fixed_base;
+pub mod fixture;
+mod generators;
+pub mod key;
+mod narrow;
 pub mod pasta;
+pub mod pedersen;
+mod pippenger;
+mod projective;
+mod small;
+mod sparse;
 pub mod utils;
 
+pub use accumulator::MsmAccumulator;
+pub use claims::{
+    find_invalid_claim, find_invalid_claim_with_rng, verify_claims,
+    verify_claims_with_rng, MsmClaim,
+};
+pub use config::{
+    default_backend, gpu_available, gpu_threshold, set_default_backend,
+    set_gpu_threshold, Backend, MsmConfig, Threads, DEFAULT_GPU_THRESHOLD,
+};
+pub use curve::CurveId;
+pub use error::MsmError;
+pub use fixed_base::FixedBaseMsm;
+pub use generators::generators;
+pub use small::{
+    set_small_msm_threshold, small_msm_threshold, DEFAULT_SMALL_MSM_THRESHOLD,
+};
+
+#[cfg(not(any(feature = "native", feature = "pure-rust")))]
+compile_error!("either the `native` or the `pure-rust` feature is required");
+
+#[cfg(feature = "native")]
 extern crate blst;
 
 #[cfg(feature = "cuda")]
@@ -15,28 +53,523 @@ sppark::cuda_error!();
 extern "C" {
     pub fn cuda_available() -> bool;
 }
-#[cfg(feature = "cuda")]
-pub static mut CUDA_OFF: bool = false;
 
 use halo2curves::bn256;
-use halo2curves::CurveExt;
+use halo2curves::ff::PrimeField;
+use halo2curves::group::{Curve, Group};
+use halo2curves::{CurveAffine, CurveExt};
+use rayon::prelude::*;
+
+use crate::error::{check_byte_lengths, check_lengths};
+
+/// Multi-scalar multiplication for the affine points of a supported curve,
+/// dispatching to the matching backend for `Self`.
+///
+/// Points may include the identity, encoded as `(0, 0)` by both curve
+/// libraries and by the C++ backend alike, as well as repeated and opposite
+/// points, which every backend adds up with complete formulas.
+pub trait Msm: ffi::Pippenger {
+    /// Identifier of the curve in serialized formats.
+    const CURVE_ID: CurveId;
+
+    fn try_msm(
+        points: &[Self],
+        scalars: &[Self::Scalar],
+    ) -> Result<Self::Curve, MsmError> {
+        Self::try_msm_with_config(points, scalars, &MsmConfig::default())
+    }
+
+    fn msm(points: &[Self], scalars: &[Self::Scalar]) -> Self::Curve {
+        Self::try_msm(points, scalars).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`Msm::try_msm`], with the options of `config`.
+    fn try_msm_with_config(
+        points: &[Self],
+        scalars: &[Self::Scalar],
+        config: &MsmConfig,
+    ) -> Result<Self::Curve, MsmError> {
+        let npoints = check_lengths(points.len(), scalars.len())?;
+        config.check_points(points)?;
+        let gpu = config.use_gpu(npoints)?;
+        if config.is_small(npoints) {
+            return Ok(small::msm(points, scalars));
+        }
+
+        #[cfg(feature = "cuda")]
+        if gpu {
+            return unsafe { Self::cuda_pippenger(points, scalars) };
+        }
+        match config.threads {
+            Threads::Rayon => config::par_ranges(npoints, |range| {
+                ffi::mult_pippenger(&points[range.clone()], &scalars[range], 1)
+            }),
+            threads => ffi::mult_pippenger(points, scalars, threads.nthreads()),
+        }
+    }
+
+    /// Checks that every point is on the curve, as done before dispatching
+    /// by the `*_with_config` entry points with [`MsmConfig::validate`] set.
+    /// Returns [`MsmError::InvalidPoint`] with the first offending index.
+    fn validate_points(points: &[Self]) -> Result<(), MsmError> {
+        error::check_points(points)
+    }
+
+    /// Like [`Msm::try_msm`], but returns the result in affine form.
+    fn try_msm_affine(
+        points: &[Self],
+        scalars: &[Self::Scalar],
+    ) -> Result<Self, MsmError> {
+        Self::try_msm_affine_with_config(points, scalars, &MsmConfig::default())
+    }
+
+    fn msm_affine(points: &[Self], scalars: &[Self::Scalar]) -> Self {
+        Self::try_msm_affine(points, scalars)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`Msm::try_msm_affine`], with the options of `config`.
+    fn try_msm_affine_with_config(
+        points: &[Self],
+        scalars: &[Self::Scalar],
+        config: &MsmConfig,
+    ) -> Result<Self, MsmError> {
+        Ok(Self::try_msm_with_config(points, scalars, config)?.to_affine())
+    }
+
+    /// Like [`Msm::try_msm`], but takes points in projective form, which
+    /// are normalized in parallel first. Spares callers holding freshly
+    /// derived points a serial `batch_normalize`.
+    fn try_msm_projective(
+        points: &[Self::Curve],
+        scalars: &[Self::Scalar],
+    ) -> Result<Self::Curve, MsmError> {
+        projective::try_msm::<Self>(points, scalars, &MsmConfig::default())
+    }
+
+    fn msm_projective(
+        points: &[Self::Curve],
+        scalars: &[Self::Scalar],
+    ) -> Self::Curve {
+        Self::try_msm_projective(points, scalars)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`Msm::try_msm_projective`], with the options of `config`.
+    fn try_msm_projective_with_config(
+        points: &[Self::Curve],
+        scalars: &[Self::Scalar],
+        config: &MsmConfig,
+    ) -> Result<Self::Curve, MsmError> {
+        projective::try_msm::<Self>(points, scalars, config)
+    }
+
+    /// Like [`Msm::try_msm`], but drops zero scalars, sums up the points of
+    /// unit scalars and runs scalars of up to 64 bits with proportionally
+    /// fewer windows. Pays off for vectors dominated by small values.
+    fn try_msm_sparse(
+        points: &[Self],
+        scalars: &[Self::Scalar],
+    ) -> Result<Self::Curve, MsmError> {
+        sparse::try_msm(points, scalars, &MsmConfig::default())
+    }
+
+    fn msm_sparse(points: &[Self], scalars: &[Self::Scalar]) -> Self::Curve {
+        Self::try_msm_sparse(points, scalars)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`Msm::try_msm_sparse`], with the options of `config`.
+    fn try_msm_sparse_with_config(
+        points: &[Self],
+        scalars: &[Self::Scalar],
+        config: &MsmConfig,
+    ) -> Result<Self::Curve, MsmError> {
+        sparse::try_msm(points, scalars, config)
+    }
+
+    /// Computes the MSM of `points` with native 64-bit scalars, using a
+    /// quarter of the windows needed for full-width field elements.
+    fn try_msm_u64(
+        points: &[Self],
+        scalars: &[u64],
+    ) -> Result<Self::Curve, MsmError> {
+        narrow::try_msm_u64(points, scalars, &MsmConfig::default())
+    }
+
+    fn msm_u64(points: &[Self], scalars: &[u64]) -> Self::Curve {
+        Self::try_msm_u64(points, scalars).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`Msm::try_msm_u64`], with the options of `config`.
+    fn try_msm_u64_with_config(
+        points: &[Self],
+        scalars: &[u64],
+        config: &MsmConfig,
+    ) -> Result<Self::Curve, MsmError> {
+        narrow::try_msm_u64(points, scalars, config)
+    }
+
+    /// Computes the MSM of `points` with scalars known to fit in `max_bits`
+    /// bits, with a number of windows proportional to `max_bits`, which is
+    /// capped at the width of the scalar field. Fails with
+    /// [`MsmError::ScalarTooWide`] if any scalar is wider.
+    fn try_msm_bits(
+        points: &[Self],
+        scalars: &[Self::Scalar],
+        max_bits: usize,
+    ) -> Result<Self::Curve, MsmError> {
+        narrow::try_msm_bits(points, scalars, max_bits, &MsmConfig::default())
+    }
+
+    fn msm_bits(
+        points: &[Self],
+        scalars: &[Self::Scalar],
+        max_bits: usize,
+    ) -> Self::Curve {
+        Self::try_msm_bits(points, scalars, max_bits)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`Msm::try_msm_bits`], with the options of `config`.
+    fn try_msm_bits_with_config(
+        points: &[Self],
+        scalars: &[Self::Scalar],
+        max_bits: usize,
+        config: &MsmConfig,
+    ) -> Result<Self::Curve, MsmError> {
+        narrow::try_msm_bits(points, scalars, max_bits, config)
+    }
+
+    /// Computes the MSM of `points` with scalars given as canonical
+    /// little-endian limbs, e.g. as deserialized, which saves the pass
+    /// converting them out of Montgomery form. Fails with
+    /// [`MsmError::NonCanonicalScalar`] if a scalar is not less than the
+    /// modulus. Runs on the CPU regardless of the backend.
+    fn try_msm_canonical(
+        points: &[Self],
+        scalars: &[[u64; 4]],
+    ) -> Result<Self::Curve, MsmError> {
+        canonical::try_msm(points, scalars, &MsmConfig::default())
+    }
+
+    fn msm_canonical(points: &[Self], scalars: &[[u64; 4]]) -> Self::Curve {
+        Self::try_msm_canonical(points, scalars)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`Msm::try_msm_canonical`], with the options of `config`.
+    fn try_msm_canonical_with_config(
+        points: &[Self],
+        scalars: &[[u64; 4]],
+        config: &MsmConfig,
+    ) -> Result<Self::Curve, MsmError> {
+        canonical::try_msm(points, scalars, config)
+    }
+
+    /// Computes one MSM of `points` per scalar vector in `scalars`. Each
+    /// Pippenger window loads every point once and adds it to the buckets
+    /// of all vectors in the same pass. The C++ backend keeps the buckets
+    /// in affine form, with the inversions shared across the additions of
+    /// the whole batch, which makes this faster than calling
+    /// [`Msm::try_msm`] for every vector. Inputs served by the small-MSM
+    /// path or the GPU are still computed one by one.
+    fn try_batch_msm(
+        points: &[Self],
+        scalars: &[&[Self::Scalar]],
+    ) -> Result<Vec<Self::Curve>, MsmError> {
+        Self::try_batch_msm_with_config(points, scalars, &MsmConfig::default())
+    }
+
+    fn batch_msm(
+        points: &[Self],
+        scalars: &[&[Self::Scalar]],
+    ) -> Vec<Self::Curve> {
+        Self::try_batch_msm(points, scalars).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`Msm::try_batch_msm`], with the options of `config`. Under
+    /// [`Threads::Rayon`] the points are split into one chunk per rayon
+    /// thread, each running the whole batch single-threaded.
+    fn try_batch_msm_with_config(
+        points: &[Self],
+        scalars: &[&[Self::Scalar]],
+        config: &MsmConfig,
+    ) -> Result<Vec<Self::Curve>, MsmError> {
+        let npoints = points.len();
+        for scalars in scalars {
+            check_lengths(npoints, scalars.len())?;
+        }
+        config.check_points(points)?;
+        let config = &MsmConfig {
+            validate: false,
+            ..*config
+        };
+        let gpu = config.use_gpu(npoints)?;
+        if config.is_small(npoints) || scalars.is_empty() {
+            return scalars
+                .iter()
+                .map(|s| Self::try_msm_with_config(points, s, config))
+                .collect();
+        }
+
+        #[cfg(feature = "cuda")]
+        if gpu {
+            return scalars
+                .iter()
+                .map(|s| Self::try_msm_with_config(points, s, config))
+                .collect();
+        }
+        match config.threads {
+            Threads::Rayon => {
+                config::par_ranges_batch(npoints, scalars.len(), |range| {
+                    let scalars = scalars
+                        .iter()
+                        .map(|s| &s[range.clone()])
+                        .collect::<Vec<_>>();
+                    ffi::mult_pippenger_batch(&points[range], &scalars, 1)
+                })
+            }
+            threads => {
+                ffi::mult_pippenger_batch(points, scalars, threads.nthreads())
+            }
+        }
+    }
+
+    /// Like [`Msm::try_batch_msm`], but returns the results in affine form,
+    /// normalized with a single inversion.
+    fn try_batch_msm_affine(
+        points: &[Self],
+        scalars: &[&[Self::Scalar]],
+    ) -> Result<Vec<Self>, MsmError> {
+        Self::try_batch_msm_affine_with_config(
+            points,
+            scalars,
+            &MsmConfig::default(),
+        )
+    }
+
+    /// Like [`Msm::try_batch_msm_affine`], with the options of `config`.
+    fn try_batch_msm_affine_with_config(
+        points: &[Self],
+        scalars: &[&[Self::Scalar]],
+        config: &MsmConfig,
+    ) -> Result<Vec<Self>, MsmError> {
+        let ret = Self::try_batch_msm_with_config(points, scalars, config)?;
+        let mut affine = vec![Self::identity(); ret.len()];
+        Self::Curve::batch_normalize(&ret, &mut affine);
+        Ok(affine)
+    }
 
+    /// Computes the MSM of `points` with scalars given as canonical
+    /// little-endian byte strings of `(nbits + 7) / 8` bytes each, laid out
+    /// back to back. Pippenger windows only cover the low `nbits` bits.
+    /// Fails with [`MsmError::WidthTooLarge`] if `nbits` exceeds the width
+    /// of the scalar field.
+    fn try_msm_bytes(
+        points: &[Self],
+        scalars: &[u8],
+        nbits: usize,
+    ) -> Result<Self::Curve, MsmError> {
+        Self::try_msm_bytes_with_config(
+            points,
+            scalars,
+            nbits,
+            &MsmConfig::default(),
+        )
+    }
+
+    /// Like [`Msm::try_msm_bytes`], with the options of `config`.
+    fn try_msm_bytes_with_config(
+        points: &[Self],
+        scalars: &[u8],
+        nbits: usize,
+        config: &MsmConfig,
+    ) -> Result<Self::Curve, MsmError> {
+        let max_bits = <Self::Scalar as PrimeField>::NUM_BITS as usize;
+        if nbits > max_bits {
+            return Err(MsmError::WidthTooLarge { nbits, max_bits });
+        }
+        let npoints = check_byte_lengths(points.len(), scalars.len(), nbits)?;
+        config.check_points(points)?;
+        if npoints == 0 || nbits == 0 {
+            return Ok(Self::Curve::identity());
+        }
+
+        let nbytes = (nbits + 7) / 8;
+        match config.threads {
+            Threads::Rayon => config::par_ranges(npoints, |range| {
+                ffi::mult_pippenger_bits(
+                    &points[range.clone()],
+                    &scalars[range.start * nbytes..range.end * nbytes],
+                    nbits,
+                    1,
+                )
+            }),
+            threads => ffi::mult_pippenger_bits(
+                points,
+                scalars,
+                nbits,
+                threads.nthreads(),
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "native")]
 extern "C" {
     fn mult_pippenger_bn254(
         out: *mut bn256::G1,
         points: *const bn256::G1Affine,
         npoints: usize,
         scalars: *const bn256::Fr,
+        is_mont: bool,
+        nthreads: usize,
     );
 
+    fn mult_pippenger_batch_bn254(
+        out: *mut bn256::G1,
+        points: *const bn256::G1Affine,
+        npoints: usize,
+        scalars: *const *const bn256::Fr,
+        nbatches: usize,
+        nthreads: usize,
+    );
+
+    fn mult_pippenger_bits_bn254(
+        out: *mut bn256::G1,
+        points: *const bn256::G1Affine,
+        npoints: usize,
+        scalars: *const u8,
+        nbits: usize,
+        nthreads: usize,
+    );
+
+    fn mult_fixed_base_bn254(
+        out: *mut bn256::G1,
+        table: *const bn256::G1Affine,
+        npoints: usize,
+        ncopies: usize,
+        digits: *const i32,
+        window: usize,
+        nrounds: usize,
+        nthreads: usize,
+    );
 }
 
 pub fn bn256(points: &[bn256::G1Affine], scalars: &[bn256::Fr]) -> bn256::G1 {
-    let npoints = points.len();
-    assert!(npoints == scalars.len(), "length mismatch");
+    bn256::G1Affine::msm(points, scalars)
+}
+
+/// Like [`bn256()`], but reports errors instead of panicking.
+pub fn try_bn256(
+    points: &[bn256::G1Affine],
+    scalars: &[bn256::Fr],
+) -> Result<bn256::G1, MsmError> {
+    bn256::G1Affine::try_msm(points, scalars)
+}
+
+#[cfg(feature = "native")]
+impl ffi::Pippenger for bn256::G1Affine {
+    unsafe fn mult_pippenger(
+        points: &[Self],
+        scalars: &[bn256::Fr],
+        nthreads: usize,
+    ) -> Result<bn256::G1, MsmError> {
+        let mut ret = bn256::G1::default();
+        mult_pippenger_bn254(
+            &mut ret,
+            &points[0],
+            points.len(),
+            &scalars[0],
+            true,
+            nthreads,
+        );
+        ffi::from_jacobian((ret.x, ret.y, ret.z))
+    }
+
+    unsafe fn mult_pippenger_canonical(
+        points: &[Self],
+        scalars: &[[u64; 4]],
+        nthreads: usize,
+    ) -> Result<bn256::G1, MsmError> {
+        let mut ret = bn256::G1::default();
+        mult_pippenger_bn254(
+            &mut ret,
+            &points[0],
+            points.len(),
+            scalars.as_ptr() as *const bn256::Fr,
+            false,
+            nthreads,
+        );
+        ffi::from_jacobian((ret.x, ret.y, ret.z))
+    }
+
+    unsafe fn mult_pippenger_bits(
+        points: &[Self],
+        scalars: &[u8],
+        nbits: usize,
+        nthreads: usize,
+    ) -> Result<bn256::G1, MsmError> {
+        let mut ret = bn256::G1::default();
+        mult_pippenger_bits_bn254(
+            &mut ret,
+            &points[0],
+            points.len(),
+            &scalars[0],
+            nbits,
+            nthreads,
+        );
+        ffi::from_jacobian((ret.x, ret.y, ret.z))
+    }
+
+    unsafe fn mult_pippenger_batch(
+        points: &[Self],
+        scalars: &[&[bn256::Fr]],
+        nthreads: usize,
+    ) -> Result<Vec<bn256::G1>, MsmError> {
+        let mut ret = vec![bn256::G1::default(); scalars.len()];
+        let scalars = scalars.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+        mult_pippenger_batch_bn254(
+            ret.as_mut_ptr(),
+            points.as_ptr(),
+            points.len(),
+            scalars.as_ptr(),
+            scalars.len(),
+            nthreads,
+        );
+        ret.into_iter()
+            .map(|ret| ffi::from_jacobian((ret.x, ret.y, ret.z)))
+            .collect()
+    }
+
+    unsafe fn mult_fixed_base(
+        table: &[Self],
+        ncopies: usize,
+        digits: &[i32],
+        window: usize,
+        nrounds: usize,
+        nthreads: usize,
+    ) -> Result<bn256::G1, MsmError> {
+        let mut ret = bn256::G1::default();
+        mult_fixed_base_bn254(
+            &mut ret,
+            &table[0],
+            table.len() / ncopies,
+            ncopies,
+            &digits[0],
+            window,
+            nrounds,
+            nthreads,
+        );
+        ffi::from_jacobian((ret.x, ret.y, ret.z))
+    }
 
     #[cfg(feature = "cuda")]
-    if npoints >= 1 << 16 && unsafe { !CUDA_OFF && cuda_available() } {
+    unsafe fn cuda_pippenger(
+        points: &[Self],
+        scalars: &[bn256::Fr],
+    ) -> Result<bn256::G1, MsmError> {
         extern "C" {
             fn cuda_pippenger_bn254(
                 out: *mut bn256::G1,
@@ -47,39 +580,186 @@ pub fn bn256(points: &[bn256::G1Affine], scalars: &[bn256::Fr]) -> bn256::G1 {
 
         }
         let mut ret = bn256::G1::default();
-        let err = unsafe {
-            cuda_pippenger_bn254(&mut ret, &points[0], npoints, &scalars[0])
-        };
-        assert!(err.code == 0, "{}", String::from(err));
+        let err = cuda_pippenger_bn254(
+            &mut ret,
+            &points[0],
+            points.len(),
+            &scalars[0],
+        );
+        if err.code != 0 {
+            return Err(err.into());
+        }
 
-        return bn256::G1::new_jacobian(ret.x, ret.y, ret.z).unwrap();
+        ffi::from_jacobian((ret.x, ret.y, ret.z))
     }
-    let mut ret = bn256::G1::default();
-    unsafe { mult_pippenger_bn254(&mut ret, &points[0], npoints, &scalars[0]) };
-    bn256::G1::new_jacobian(ret.x, ret.y, ret.z).unwrap()
+}
+
+#[cfg(not(feature = "native"))]
+impl ffi::Pippenger for bn256::G1Affine {}
+
+impl Msm for bn256::G1Affine {
+    const CURVE_ID: CurveId = CurveId::Bn256;
 }
 
 use halo2curves::grumpkin;
 
+#[cfg(feature = "native")]
 extern "C" {
     fn mult_pippenger_grumpkin(
         out: *mut grumpkin::G1,
         points: *const grumpkin::G1Affine,
         npoints: usize,
         scalars: *const grumpkin::Fr,
+        is_mont: bool,
+        nthreads: usize,
+    );
+
+    fn mult_pippenger_batch_grumpkin(
+        out: *mut grumpkin::G1,
+        points: *const grumpkin::G1Affine,
+        npoints: usize,
+        scalars: *const *const grumpkin::Fr,
+        nbatches: usize,
+        nthreads: usize,
+    );
+
+    fn mult_pippenger_bits_grumpkin(
+        out: *mut grumpkin::G1,
+        points: *const grumpkin::G1Affine,
+        npoints: usize,
+        scalars: *const u8,
+        nbits: usize,
+        nthreads: usize,
     );
 
+    fn mult_fixed_base_grumpkin(
+        out: *mut grumpkin::G1,
+        table: *const grumpkin::G1Affine,
+        npoints: usize,
+        ncopies: usize,
+        digits: *const i32,
+        window: usize,
+        nrounds: usize,
+        nthreads: usize,
+    );
 }
 
 pub fn grumpkin(
     points: &[grumpkin::G1Affine],
     scalars: &[grumpkin::Fr],
 ) -> grumpkin::G1 {
-    let npoints = points.len();
-    assert!(npoints == scalars.len(), "length mismatch");
+    grumpkin::G1Affine::msm(points, scalars)
+}
+
+/// Like [`grumpkin()`], but reports errors instead of panicking.
+pub fn try_grumpkin(
+    points: &[grumpkin::G1Affine],
+    scalars: &[grumpkin::Fr],
+) -> Result<grumpkin::G1, MsmError> {
+    grumpkin::G1Affine::try_msm(points, scalars)
+}
+
+#[cfg(feature = "native")]
+impl ffi::Pippenger for grumpkin::G1Affine {
+    unsafe fn mult_pippenger(
+        points: &[Self],
+        scalars: &[grumpkin::Fr],
+        nthreads: usize,
+    ) -> Result<grumpkin::G1, MsmError> {
+        let mut ret = grumpkin::G1::default();
+        mult_pippenger_grumpkin(
+            &mut ret,
+            &points[0],
+            points.len(),
+            &scalars[0],
+            true,
+            nthreads,
+        );
+        ffi::from_jacobian((ret.x, ret.y, ret.z))
+    }
+
+    unsafe fn mult_pippenger_canonical(
+        points: &[Self],
+        scalars: &[[u64; 4]],
+        nthreads: usize,
+    ) -> Result<grumpkin::G1, MsmError> {
+        let mut ret = grumpkin::G1::default();
+        mult_pippenger_grumpkin(
+            &mut ret,
+            &points[0],
+            points.len(),
+            scalars.as_ptr() as *const grumpkin::Fr,
+            false,
+            nthreads,
+        );
+        ffi::from_jacobian((ret.x, ret.y, ret.z))
+    }
+
+    unsafe fn mult_pippenger_bits(
+        points: &[Self],
+        scalars: &[u8],
+        nbits: usize,
+        nthreads: usize,
+    ) -> Result<grumpkin::G1, MsmError> {
+        let mut ret = grumpkin::G1::default();
+        mult_pippenger_bits_grumpkin(
+            &mut ret,
+            &points[0],
+            points.len(),
+            &scalars[0],
+            nbits,
+            nthreads,
+        );
+        ffi::from_jacobian((ret.x, ret.y, ret.z))
+    }
+
+    unsafe fn mult_pippenger_batch(
+        points: &[Self],
+        scalars: &[&[grumpkin::Fr]],
+        nthreads: usize,
+    ) -> Result<Vec<grumpkin::G1>, MsmError> {
+        let mut ret = vec![grumpkin::G1::default(); scalars.len()];
+        let scalars = scalars.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+        mult_pippenger_batch_grumpkin(
+            ret.as_mut_ptr(),
+            points.as_ptr(),
+            points.len(),
+            scalars.as_ptr(),
+            scalars.len(),
+            nthreads,
+        );
+        ret.into_iter()
+            .map(|ret| ffi::from_jacobian((ret.x, ret.y, ret.z)))
+            .collect()
+    }
+
+    unsafe fn mult_fixed_base(
+        table: &[Self],
+        ncopies: usize,
+        digits: &[i32],
+        window: usize,
+        nrounds: usize,
+        nthreads: usize,
+    ) -> Result<grumpkin::G1, MsmError> {
+        let mut ret = grumpkin::G1::default();
+        mult_fixed_base_grumpkin(
+            &mut ret,
+            &table[0],
+            table.len() / ncopies,
+            ncopies,
+            &digits[0],
+            window,
+            nrounds,
+            nthreads,
+        );
+        ffi::from_jacobian((ret.x, ret.y, ret.z))
+    }
 
     #[cfg(feature = "cuda")]
-    if npoints >= 1 << 16 && unsafe { !CUDA_OFF && cuda_available() } {
+    unsafe fn cuda_pippenger(
+        points: &[Self],
+        scalars: &[grumpkin::Fr],
+    ) -> Result<grumpkin::G1, MsmError> {
         extern "C" {
             fn cuda_pippenger_grumpkin(
                 out: *mut grumpkin::G1,
@@ -90,42 +770,742 @@ pub fn grumpkin(
 
         }
         let mut ret = grumpkin::G1::default();
-        let err = unsafe {
-            cuda_pippenger_grumpkin(&mut ret, &points[0], npoints, &scalars[0])
-        };
-        assert!(err.code == 0, "{}", String::from(err));
+        let err = cuda_pippenger_grumpkin(
+            &mut ret,
+            &points[0],
+            points.len(),
+            &scalars[0],
+        );
+        if err.code != 0 {
+            return Err(err.into());
+        }
 
-        return grumpkin::G1::new_jacobian(ret.x, ret.y, ret.z).unwrap();
+        ffi::from_jacobian((ret.x, ret.y, ret.z))
     }
-    let mut ret = grumpkin::G1::default();
-    unsafe {
-        mult_pippenger_grumpkin(&mut ret, &points[0], npoints, &scalars[0])
-    };
-    grumpkin::G1::new_jacobian(ret.x, ret.y, ret.z).unwrap()
+}
+
+#[cfg(not(feature = "native"))]
+impl ffi::Pippenger for grumpkin::G1Affine {}
+
+impl Msm for grumpkin::G1Affine {
+    const CURVE_ID: CurveId = CurveId::Grumpkin;
 }
 
 #[cfg(test)]
 mod tests {
     use halo2curves::group::Curve;
 
-    use crate::utils::{gen_points, gen_scalars, naive_multiscalar_mul};
+    use crate::utils::{
+        gen_points_seeded, gen_scalars_seeded, naive_multiscalar_mul,
+        off_curve, test_seed, TEST_SEED_VAR,
+    };
 
-    #[test]
-    fn it_works() {
+    fn check_it_works<C: crate::Msm>(msm: fn(&[C], &[C::Scalar]) -> C::Curve) {
         #[cfg(not(debug_assertions))]
         const NPOINTS: usize = 128 * 1024;
         #[cfg(debug_assertions)]
         const NPOINTS: usize = 8 * 1024;
 
-        let points = gen_points(NPOINTS);
-        let scalars = gen_scalars(NPOINTS);
+        let seed = test_seed();
+        let points = gen_points_seeded::<C>(NPOINTS, seed);
+        let scalars = gen_scalars_seeded(NPOINTS, seed);
 
         let naive = naive_multiscalar_mul(&points, &scalars);
         println!("{:?}", naive);
 
-        let ret = crate::bn256(&points, &scalars).to_affine();
+        let ret = msm(&points, &scalars).to_affine();
         println!("{:?}", ret);
 
-        assert_eq!(ret, naive);
+        assert_eq!(ret, naive, "{}={}", TEST_SEED_VAR, seed);
+    }
+
+    #[test]
+    fn it_works() {
+        check_it_works(crate::bn256);
+        check_it_works(crate::grumpkin);
+    }
+
+    #[test]
+    fn try_reports_errors() {
+        use crate::MsmError;
+
+        let points = gen_points_seeded(2, 0);
+        let scalars = gen_scalars_seeded(3, 0);
+
+        assert_eq!(
+            crate::try_bn256(&points, &scalars),
+            Err(MsmError::LengthMismatch {
+                points: 2,
+                scalars: 3
+            })
+        );
+    }
+
+    fn check_small_inputs<C: crate::Msm>() {
+        use halo2curves::group::Group;
+
+        let seed = test_seed();
+        for n in [0, 1, 2, 3, 5, 7, 9, 17, 33] {
+            let points = gen_points_seeded::<C>(n, seed);
+            let scalars = gen_scalars_seeded::<C::Scalar>(n, seed);
+
+            let naive = points
+                .iter()
+                .zip(&scalars)
+                .fold(C::Curve::identity(), |acc, (p, s)| acc + *p * s);
+
+            assert_eq!(
+                C::msm(&points, &scalars),
+                naive,
+                "n = {}, {}={}",
+                n,
+                TEST_SEED_VAR,
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn small_inputs() {
+        use halo2curves::{bn256, grumpkin};
+        use pasta_curves::{pallas, vesta};
+
+        check_small_inputs::<bn256::G1Affine>();
+        check_small_inputs::<grumpkin::G1Affine>();
+        check_small_inputs::<pallas::Affine>();
+        check_small_inputs::<vesta::Affine>();
+    }
+
+    fn check_batch<C: crate::Msm>() {
+        use halo2curves::ff::Field;
+
+        let seed = test_seed();
+        for (n, nbatches) in [(0, 4), (3, 4), (100, 4), (100, 1), (700, 9)] {
+            let points = gen_points_seeded::<C>(n, seed);
+            let scalars = (0..nbatches)
+                .map(|i| gen_scalars_seeded(n, seed ^ i))
+                .collect::<Vec<_>>();
+            let batch = scalars.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+            let expected = scalars
+                .iter()
+                .map(|scalars| C::msm(&points, scalars))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                C::batch_msm(&points, &batch),
+                expected,
+                "n = {}, nbatches = {}, {}={}",
+                n,
+                nbatches,
+                TEST_SEED_VAR,
+                seed
+            );
+            assert!(C::batch_msm(&points, &[]).is_empty());
+        }
+
+        let points = vec![C::generator(); 3];
+        let scalars = vec![C::Scalar::ONE; 3];
+        assert_eq!(
+            C::try_batch_msm(&points, &[&scalars, &scalars[1..]]),
+            Err(crate::MsmError::LengthMismatch {
+                points: 3,
+                scalars: 2
+            })
+        );
+    }
+
+    #[test]
+    fn batch_msm() {
+        use halo2curves::{bn256, grumpkin};
+        use pasta_curves::{pallas, vesta};
+
+        check_batch::<bn256::G1Affine>();
+        check_batch::<grumpkin::G1Affine>();
+        check_batch::<pallas::Affine>();
+        check_batch::<vesta::Affine>();
+    }
+
+    fn check_thread_configs<C: crate::Msm>() {
+        use halo2curves::ff::PrimeField;
+
+        use crate::{MsmConfig, Threads};
+
+        let seed = test_seed();
+        let points = gen_points_seeded::<C>(1000, seed);
+        let scalars = gen_scalars_seeded::<C::Scalar>(1000, seed);
+        let bytes = scalars
+            .iter()
+            .flat_map(|s| s.to_repr().as_ref()[..3].to_vec())
+            .collect::<Vec<_>>();
+
+        let expected = C::msm(&points, &scalars);
+        let expected_bytes = C::try_msm_bytes(&points, &bytes, 20).unwrap();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap();
+        for threads in [
+            Threads::Default,
+            Threads::Count(0),
+            Threads::Count(1),
+            Threads::Count(3),
+            Threads::Rayon,
+        ] {
+            let config = MsmConfig {
+                threads,
+                ..Default::default()
+            };
+            let msg = format!("{:?}, {}={}", threads, TEST_SEED_VAR, seed);
+            let msm =
+                || C::try_msm_with_config(&points, &scalars, &config).unwrap();
+            assert_eq!(msm(), expected, "{}", msg);
+            assert_eq!(pool.install(msm), expected, "{}", msg);
+            assert_eq!(
+                C::try_batch_msm_with_config(
+                    &points,
+                    &[&scalars, &scalars],
+                    &config
+                ),
+                Ok(vec![expected; 2]),
+                "{}",
+                msg
+            );
+            assert_eq!(
+                C::try_batch_msm_affine_with_config(
+                    &points,
+                    &[&scalars],
+                    &config
+                ),
+                Ok(vec![expected.to_affine()]),
+                "{}",
+                msg
+            );
+            assert_eq!(
+                C::try_msm_bytes_with_config(&points, &bytes, 20, &config),
+                Ok(expected_bytes),
+                "{}",
+                msg
+            );
+        }
+    }
+
+    #[test]
+    fn thread_configs() {
+        use halo2curves::{bn256, grumpkin};
+        use pasta_curves::{pallas, vesta};
+
+        check_thread_configs::<bn256::G1Affine>();
+        check_thread_configs::<grumpkin::G1Affine>();
+        check_thread_configs::<pallas::Affine>();
+        check_thread_configs::<vesta::Affine>();
+    }
+
+    #[cfg(unix)]
+    fn check_bytes_at_end<C: crate::Msm>() {
+        use rand::{RngCore, SeedableRng};
+        use rand_chacha::ChaCha20Rng;
+
+        use crate::utils::Guarded;
+        use crate::{MsmConfig, Threads};
+
+        // Scalars are placed right before an inaccessible page, so that the
+        // backend faults if it reads past the last one.
+        let seed = test_seed();
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let points = gen_points_seeded::<C>(1000, seed);
+        for n in [1, 2, 16, 31, 32, 100, 1000] {
+            for nbits in [1, 2, 3, 4, 6, 8, 12, 16, 24, 32, 64] {
+                let mut bytes = vec![0u8; n * ((nbits + 7) / 8)];
+                rng.fill_bytes(&mut bytes);
+                let expected = crate::pippenger::msm_bytes(
+                    &points[..n],
+                    &bytes,
+                    nbits,
+                    false,
+                );
+                let bytes = Guarded::new(&bytes);
+                for threads in [Threads::Count(1), Threads::Count(4)] {
+                    let config = MsmConfig {
+                        threads,
+                        ..Default::default()
+                    };
+                    assert_eq!(
+                        C::try_msm_bytes_with_config(
+                            &points[..n],
+                            &bytes,
+                            nbits,
+                            &config
+                        ),
+                        Ok(expected),
+                        "n = {}, nbits = {}, {:?}, {}={}",
+                        n,
+                        nbits,
+                        threads,
+                        TEST_SEED_VAR,
+                        seed
+                    );
+                }
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bytes_at_end_of_allocation() {
+        use halo2curves::{bn256, grumpkin};
+        use pasta_curves::{pallas, vesta};
+
+        check_bytes_at_end::<bn256::G1Affine>();
+        check_bytes_at_end::<grumpkin::G1Affine>();
+        check_bytes_at_end::<pallas::Affine>();
+        check_bytes_at_end::<vesta::Affine>();
+    }
+
+    fn check_backends<C: crate::Msm>() {
+        use crate::{gpu_available, Backend, MsmConfig, MsmError};
+
+        let seed = test_seed();
+        let points = gen_points_seeded::<C>(100, seed);
+        let scalars = gen_scalars_seeded::<C::Scalar>(100, seed);
+
+        let expected = C::msm(&points, &scalars);
+        for config in [
+            MsmConfig {
+                backend: Some(Backend::Cpu),
+                ..Default::default()
+            },
+            MsmConfig {
+                backend: Some(Backend::Auto),
+                gpu_threshold: Some(0),
+                ..Default::default()
+            },
+            MsmConfig {
+                backend: Some(Backend::Gpu),
+                ..Default::default()
+            },
+        ] {
+            let ret = C::try_msm_with_config(&points, &scalars, &config);
+            let batch =
+                C::try_batch_msm_with_config(&points, &[&scalars], &config);
+            let affine = C::try_batch_msm_affine_with_config(
+                &points,
+                &[&scalars],
+                &config,
+            );
+            if config.backend == Some(Backend::Gpu) && !gpu_available() {
+                assert_eq!(ret, Err(MsmError::GpuUnavailable));
+                assert_eq!(batch, Err(MsmError::GpuUnavailable));
+                assert_eq!(affine, Err(MsmError::GpuUnavailable));
+            } else {
+                let msg = format!("{:?}, {}={}", config, TEST_SEED_VAR, seed);
+                assert_eq!(ret, Ok(expected), "{}", msg);
+                assert_eq!(batch, Ok(vec![expected]), "{}", msg);
+                assert_eq!(affine, Ok(vec![expected.to_affine()]), "{}", msg);
+            }
+        }
+    }
+
+    #[test]
+    fn backends() {
+        use halo2curves::{bn256, grumpkin};
+        use pasta_curves::{pallas, vesta};
+
+        check_backends::<bn256::G1Affine>();
+        check_backends::<grumpkin::G1Affine>();
+        check_backends::<pallas::Affine>();
+        check_backends::<vesta::Affine>();
+    }
+
+    fn check_outputs<C: crate::Msm>() {
+        use halo2curves::CurveExt;
+
+        use crate::{MsmConfig, Threads};
+
+        let seed = test_seed();
+        for n in [0, 1, 3, 100, 1000] {
+            let points = gen_points_seeded::<C>(n, seed);
+            let scalars = gen_scalars_seeded::<C::Scalar>(n, seed);
+            let msg = format!("n = {}, {}={}", n, TEST_SEED_VAR, seed);
+            let bytes = (0..n).map(|i| i as u8).collect::<Vec<_>>();
+            let config = MsmConfig {
+                threads: Threads::Rayon,
+                ..Default::default()
+            };
+
+            let mut outputs = vec![
+                C::msm(&points, &scalars),
+                C::try_msm_with_config(&points, &scalars, &config).unwrap(),
+                C::try_msm_bytes(&points, &bytes, 8).unwrap(),
+            ];
+            outputs.extend(C::batch_msm(&points, &[&scalars, &scalars]));
+            for ret in &outputs {
+                assert!(bool::from(ret.is_on_curve()), "{}", msg);
+            }
+
+            let affine = C::msm_affine(&points, &scalars);
+            assert!(bool::from(affine.is_on_curve()), "{}", msg);
+            assert_eq!(affine, outputs[0].to_affine(), "{}", msg);
+            assert_eq!(
+                C::try_msm_affine_with_config(&points, &scalars, &config),
+                Ok(affine),
+                "{}",
+                msg
+            );
+            assert_eq!(
+                C::try_batch_msm_affine(&points, &[&scalars]),
+                Ok(vec![affine]),
+                "{}",
+                msg
+            );
+            assert_eq!(
+                C::try_batch_msm_affine_with_config(
+                    &points,
+                    &[&scalars],
+                    &config
+                ),
+                Ok(vec![affine]),
+                "{}",
+                msg
+            );
+        }
+    }
+
+    #[test]
+    fn outputs() {
+        use halo2curves::bn256;
+        use halo2curves::ff::Field;
+        use halo2curves::group::Group;
+        use halo2curves::grumpkin;
+        use pasta_curves::{pallas, vesta};
+
+        check_outputs::<bn256::G1Affine>();
+        check_outputs::<grumpkin::G1Affine>();
+        check_outputs::<pallas::Affine>();
+        check_outputs::<vesta::Affine>();
+
+        let (one, zero) = (bn256::Fq::ONE, bn256::Fq::ZERO);
+        assert_eq!(
+            crate::ffi::from_jacobian::<bn256::G1>((one, one, one)),
+            Err(crate::MsmError::InvalidOutput)
+        );
+        assert_eq!(
+            crate::ffi::from_jacobian((one, one, zero)),
+            Ok(bn256::G1::identity())
+        );
+        let (one, zero) = (pallas::Base::ONE, pallas::Base::ZERO);
+        assert_eq!(
+            crate::ffi::from_jacobian::<pallas::Point>((one, one, one)),
+            Err(crate::MsmError::InvalidOutput)
+        );
+        assert_eq!(
+            crate::ffi::from_jacobian((one, one, zero)),
+            Ok(pallas::Point::identity())
+        );
+    }
+
+    fn check_edge_points<C: crate::Msm>() {
+        use halo2curves::ff::Field;
+        use halo2curves::group::prime::PrimeCurveAffine;
+        use halo2curves::group::Curve;
+
+        use crate::canonical::limbs;
+        use crate::{FixedBaseMsm, MsmConfig, Threads};
+
+        let rayon = MsmConfig {
+            threads: Threads::Rayon,
+            ..Default::default()
+        };
+        let check = |points: &[C], scalars: &[C::Scalar], case: &str| {
+            let n = points.len();
+            let expected = naive_multiscalar_mul(points, scalars);
+            let canonical = scalars.iter().map(limbs).collect::<Vec<_>>();
+            let projective =
+                points.iter().map(|p| p.to_curve()).collect::<Vec<_>>();
+            let u64s = (0..n as u64).map(|i| i * i + 1).collect::<Vec<_>>();
+            let u64_scalars =
+                u64s.iter().map(|&s| C::Scalar::from(s)).collect::<Vec<_>>();
+
+            let outputs = [
+                C::msm(points, scalars),
+                C::try_msm_with_config(points, scalars, &rayon).unwrap(),
+                C::msm_canonical(points, &canonical),
+                C::msm_sparse(points, scalars),
+                C::msm_projective(&projective, scalars),
+                C::batch_msm(points, &[scalars])[0],
+                FixedBaseMsm::new(points).msm(scalars),
+            ];
+            for (i, ret) in outputs.iter().enumerate() {
+                assert_eq!(
+                    ret.to_affine(),
+                    expected,
+                    "{}, n = {}, #{}",
+                    case,
+                    n,
+                    i
+                );
+            }
+            assert_eq!(
+                C::msm_u64(points, &u64s).to_affine(),
+                naive_multiscalar_mul(points, &u64_scalars),
+                "{}, n = {}",
+                case,
+                n
+            );
+        };
+
+        for n in [4, 300] {
+            let points = gen_points_seeded::<C>(n, 19);
+            let scalars = gen_scalars_seeded::<C::Scalar>(n, 19);
+            let p = points[0];
+
+            let identities = vec![C::identity(); n];
+            check(&identities, &scalars, "identities");
+
+            let mut sprinkled = points.clone();
+            for point in sprinkled.iter_mut().step_by(3) {
+                *point = C::identity();
+            }
+            check(&sprinkled, &scalars, "some identities");
+
+            check(&vec![p; n], &vec![scalars[0]; n], "repeated");
+            check(&vec![p; n], &scalars, "repeated point");
+
+            let opposite = points
+                .iter()
+                .take(n / 2)
+                .flat_map(|&p| [p, -p])
+                .collect::<Vec<_>>();
+            let pairs = scalars
+                .iter()
+                .take(n / 2)
+                .flat_map(|&s| [s, s])
+                .collect::<Vec<_>>();
+            check(&opposite, &pairs, "opposite pairs");
+            check(&opposite, &scalars, "opposite points");
+
+            let mixed = [p, -p, C::identity(), p]
+                .into_iter()
+                .cycle()
+                .take(n)
+                .collect::<Vec<_>>();
+            let signs = [C::Scalar::ONE, -C::Scalar::ONE]
+                .into_iter()
+                .cycle()
+                .take(n)
+                .collect::<Vec<_>>();
+            check(&mixed, &signs, "mixed");
+            check(&mixed, &scalars, "mixed points");
+        }
+    }
+
+    #[test]
+    fn edge_points() {
+        use halo2curves::{bn256, grumpkin};
+        use pasta_curves::{pallas, vesta};
+
+        check_edge_points::<bn256::G1Affine>();
+        check_edge_points::<grumpkin::G1Affine>();
+        check_edge_points::<pallas::Affine>();
+        check_edge_points::<vesta::Affine>();
+    }
+
+    fn check_edge_scalars<C: crate::Msm>() {
+        use halo2curves::ff::PrimeField;
+        use halo2curves::group::Curve;
+
+        use crate::canonical::limbs;
+        use crate::utils::gen_edge_scalars;
+        use crate::{FixedBaseMsm, MsmConfig, Threads};
+
+        let rayon = MsmConfig {
+            threads: Threads::Rayon,
+            ..Default::default()
+        };
+        let edge = gen_edge_scalars::<C::Scalar>();
+        let points = gen_points_seeded::<C>(edge.len(), 20);
+
+        // Prefixes of the edge scalars as they come, so that the small
+        // powers of two land in wide windows too, and single edge scalars
+        // repeated for every point.
+        let mut cases = [9, 33, 100, edge.len()]
+            .map(|n| edge[..n].to_vec())
+            .to_vec();
+        cases.extend(
+            // -1, the negated top power of two and the 0xff.. pattern.
+            [edge[2], edge[edge.len() - 9], edge[edge.len() - 8]]
+                .map(|s| vec![s; 100]),
+        );
+        for scalars in &cases {
+            let n = scalars.len();
+            let points = &points[..n];
+            let expected = naive_multiscalar_mul(points, scalars);
+            let canonical = scalars.iter().map(limbs).collect::<Vec<_>>();
+            let bytes = scalars
+                .iter()
+                .flat_map(|s| s.to_repr().as_ref().to_vec())
+                .collect::<Vec<_>>();
+
+            let outputs = [
+                C::msm(points, scalars),
+                C::try_msm_with_config(points, scalars, &rayon).unwrap(),
+                C::msm_canonical(points, &canonical),
+                C::msm_sparse(points, scalars),
+                C::batch_msm(points, &[scalars])[0],
+                C::try_msm_bytes(points, &bytes, C::Scalar::NUM_BITS as usize)
+                    .unwrap(),
+                FixedBaseMsm::new(points).msm(scalars),
+            ];
+            for (i, ret) in outputs.iter().enumerate() {
+                assert_eq!(ret.to_affine(), expected, "n = {}, #{}", n, i);
+            }
+        }
+    }
+
+    #[test]
+    fn edge_scalars() {
+        use halo2curves::{bn256, grumpkin};
+        use pasta_curves::{pallas, vesta};
+
+        check_edge_scalars::<bn256::G1Affine>();
+        check_edge_scalars::<grumpkin::G1Affine>();
+        check_edge_scalars::<pallas::Affine>();
+        check_edge_scalars::<vesta::Affine>();
+    }
+
+    fn check_validation<C: crate::Msm>() {
+        use halo2curves::group::prime::PrimeCurveAffine;
+
+        use crate::canonical::limbs;
+        use crate::{MsmConfig, MsmError, Threads};
+
+        let mut points = gen_points_seeded::<C>(100, 18);
+        let scalars = gen_scalars_seeded::<C::Scalar>(100, 18);
+        let canonical = scalars.iter().map(limbs).collect::<Vec<_>>();
+        let small = [200u64; 100];
+        let narrow = [C::Scalar::from(200); 100];
+        points[3] = C::identity();
+        assert_eq!(C::validate_points(&points), Ok(()));
+
+        for i in [37, 99] {
+            points[i] = off_curve(points[i]);
+        }
+        let err = Some(MsmError::InvalidPoint { index: 37 });
+        assert_eq!(C::validate_points(&points).err(), err);
+
+        for threads in [Threads::Default, Threads::Rayon] {
+            let config = MsmConfig {
+                threads,
+                validate: true,
+                ..Default::default()
+            };
+            let bytes = [0; 100];
+            assert_eq!(
+                C::try_msm_with_config(&points, &scalars, &config).err(),
+                err
+            );
+            assert_eq!(
+                C::try_msm_affine_with_config(&points, &scalars, &config).err(),
+                err
+            );
+            assert_eq!(
+                C::try_batch_msm_with_config(&points, &[&scalars], &config)
+                    .err(),
+                err
+            );
+            assert_eq!(
+                C::try_batch_msm_affine_with_config(
+                    &points,
+                    &[&scalars],
+                    &config
+                )
+                .err(),
+                err
+            );
+            assert_eq!(
+                C::try_msm_bytes_with_config(&points, &bytes, 8, &config).err(),
+                err
+            );
+            assert_eq!(
+                C::try_msm_canonical_with_config(&points, &canonical, &config)
+                    .err(),
+                err
+            );
+            assert_eq!(
+                C::try_msm_sparse_with_config(&points, &scalars, &config).err(),
+                err
+            );
+            assert_eq!(
+                C::try_msm_u64_with_config(&points, &small, &config).err(),
+                err
+            );
+            assert_eq!(
+                C::try_msm_bits_with_config(&points, &narrow, 8, &config).err(),
+                err
+            );
+            assert_eq!(
+                C::try_msm_with_config(&points[..37], &scalars[..37], &config),
+                Ok(C::msm(&points[..37], &scalars[..37]))
+            );
+            assert_eq!(
+                C::try_msm_sparse_with_config(
+                    &points[..37],
+                    &scalars[..37],
+                    &config
+                ),
+                Ok(C::msm(&points[..37], &scalars[..37]))
+            );
+        }
+        // Without validation the points go to the backend as they are.
+        assert_ne!(C::try_msm(&points, &scalars).err(), err);
+    }
+
+    #[test]
+    fn validation() {
+        use halo2curves::{bn256, grumpkin};
+        use pasta_curves::{pallas, vesta};
+
+        check_validation::<bn256::G1Affine>();
+        check_validation::<grumpkin::G1Affine>();
+        check_validation::<pallas::Affine>();
+        check_validation::<vesta::Affine>();
+    }
+
+    #[test]
+    fn default_backend() {
+        use crate::{default_backend, set_default_backend, Backend};
+
+        assert_eq!(default_backend(), Backend::Auto);
+        set_default_backend(Backend::Cpu);
+        assert_eq!(default_backend(), Backend::Cpu);
+        set_default_backend(Backend::Auto);
+    }
+
+    #[test]
+    fn msm_trait_dispatch() {
+        use crate::Msm;
+        use halo2curves::bn256;
+
+        fn generic<C: Msm>(points: &[C], scalars: &[C::Scalar]) -> C::Curve {
+            C::msm(points, scalars)
+        }
+
+        let seed = test_seed();
+        let points = gen_points_seeded(100, seed);
+        let scalars = gen_scalars_seeded(100, seed);
+
+        assert_eq!(
+            generic(&points, &scalars),
+            crate::bn256(&points, &scalars),
+            "{}={}",
+            TEST_SEED_VAR,
+            seed
+        );
+        assert_eq!(
+            bn256::G1Affine::try_msm(&points[1..], &scalars),
+            Err(crate::MsmError::LengthMismatch {
+                points: 99,
+                scalars: 100
+            })
+        );
     }
 }
@@ -0,0 +1,212 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use halo2curves::ff::PrimeField;
+use rayon::prelude::*;
+
+use crate::error::check_lengths;
+use crate::sparse::bit_length;
+use crate::{Msm, MsmConfig, MsmError};
+
+pub(crate) fn try_msm_u64<C: Msm>(
+    points: &[C],
+    scalars: &[u64],
+    config: &MsmConfig,
+) -> Result<C::Curve, MsmError> {
+    check_lengths(points.len(), scalars.len())?;
+
+    let bytes = scalars
+        .par_iter()
+        .flat_map_iter(|scalar| scalar.to_le_bytes())
+        .collect::<Vec<_>>();
+    C::try_msm_bytes_with_config(points, &bytes, 64, config)
+}
+
+pub(crate) fn try_msm_bits<C: Msm>(
+    points: &[C],
+    scalars: &[C::Scalar],
+    max_bits: usize,
+    config: &MsmConfig,
+) -> Result<C::Curve, MsmError> {
+    check_lengths(points.len(), scalars.len())?;
+    config.check_points(points)?;
+    // No scalar is wider than the field, so neither are the windows.
+    let max_bits = max_bits.min(C::Scalar::NUM_BITS as usize);
+
+    let reprs = scalars.par_iter().map(|s| s.to_repr()).collect::<Vec<_>>();
+    if let Some(index) = reprs
+        .par_iter()
+        .position_first(|repr| bit_length(repr.as_ref()) > max_bits)
+    {
+        return Err(MsmError::ScalarTooWide { index, max_bits });
+    }
+
+    let nbytes = (max_bits + 7) / 8;
+    let mut bytes = vec![0u8; scalars.len() * nbytes];
+    if nbytes > 0 {
+        bytes.par_chunks_mut(nbytes).zip(reprs.par_iter()).for_each(
+            |(bytes, repr)| {
+                let len = nbytes.min(repr.as_ref().len());
+                bytes[..len].copy_from_slice(&repr.as_ref()[..len]);
+            },
+        );
+    }
+    C::try_msm_bytes_with_config(
+        points,
+        &bytes,
+        max_bits,
+        &MsmConfig {
+            validate: false,
+            ..*config
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256;
+    use halo2curves::ff::Field;
+    use halo2curves::group::Group;
+    use halo2curves::grumpkin;
+    use pasta_curves::{pallas, vesta};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    use super::*;
+    use crate::utils::{
+        gen_edge_scalars, gen_points_seeded, test_seed, TEST_SEED_VAR,
+    };
+
+    fn check_narrow<C: Msm>() {
+        let seed = test_seed();
+        let msg = format!("{}={}", TEST_SEED_VAR, seed);
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let points = gen_points_seeded::<C>(200, seed);
+        let values = (0..200)
+            .map(|_| rng.gen::<u64>() >> rng.gen_range(0..64))
+            .collect::<Vec<_>>();
+        let scalars = values
+            .iter()
+            .map(|&v| C::Scalar::from(v))
+            .collect::<Vec<_>>();
+
+        let expected = C::msm(&points, &scalars);
+        assert_eq!(C::msm_u64(&points, &values), expected, "{}", msg);
+        assert_eq!(C::msm_bits(&points, &scalars, 64), expected, "{}", msg);
+        assert_eq!(C::msm_bits(&points, &scalars, 70), expected, "{}", msg);
+        let small = values.iter().map(|v| v % 1000).collect::<Vec<_>>();
+        let scalars = small
+            .iter()
+            .map(|&v| C::Scalar::from(v))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            C::msm_bits(&points, &scalars, 10),
+            C::msm_u64(&points, &small),
+            "{}",
+            msg
+        );
+
+        let index = scalars.iter().position(|s| *s >= C::Scalar::from(512));
+        assert_eq!(
+            C::try_msm_bits(&points, &scalars, 9),
+            Err(MsmError::ScalarTooWide {
+                index: index.unwrap(),
+                max_bits: 9
+            }),
+            "{}",
+            msg
+        );
+        assert_eq!(
+            C::msm_bits(&points, &[C::Scalar::ZERO; 200], 0),
+            C::Curve::identity()
+        );
+
+        // Widths past the scalar field are clamped by msm_bits, but rejected
+        // by msm_bytes, whose scalars would not fit in the field.
+        let edge = gen_edge_scalars::<C::Scalar>();
+        let points = &gen_points_seeded::<C>(edge.len(), seed);
+        let expected = C::msm(points, &edge);
+        for max_bits in [300, usize::MAX] {
+            assert_eq!(
+                C::msm_bits(points, &edge, max_bits),
+                expected,
+                "{}",
+                msg
+            );
+        }
+        let nbits = C::Scalar::NUM_BITS as usize;
+        let bytes = vec![0u8; points.len() * ((nbits + 7) / 8)];
+        assert_eq!(
+            C::try_msm_bytes(points, &bytes, nbits),
+            Ok(C::Curve::identity())
+        );
+        for width in [nbits + 1, 300, usize::MAX] {
+            assert_eq!(
+                C::try_msm_bytes(points, &bytes, width),
+                Err(MsmError::WidthTooLarge {
+                    nbits: width,
+                    max_bits: nbits
+                })
+            );
+        }
+    }
+
+    /// Few points with byte-aligned widths, where the last window of the
+    /// backend ends right at the end of the scalars.
+    #[cfg(unix)]
+    fn check_narrow_small<C: Msm>() {
+        use crate::utils::Guarded;
+
+        let seed = test_seed();
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let points = gen_points_seeded::<C>(32, seed);
+        for n in [1, 2, 3, 7, 8, 31, 32] {
+            for nbits in [8, 16, 24, 32, 64] {
+                let msg = format!(
+                    "n = {}, nbits = {}, {}={}",
+                    n, nbits, TEST_SEED_VAR, seed
+                );
+                let values = (0..n)
+                    .map(|_| rng.gen::<u64>() >> (64 - nbits))
+                    .collect::<Vec<_>>();
+                let scalars = values
+                    .iter()
+                    .map(|&v| C::Scalar::from(v))
+                    .collect::<Vec<_>>();
+                let expected = C::msm(&points[..n], &scalars);
+                let values = Guarded::new(&values);
+                assert_eq!(
+                    C::msm_u64(&points[..n], &values),
+                    expected,
+                    "{}",
+                    msg
+                );
+                let scalars = Guarded::new(&scalars);
+                assert_eq!(
+                    C::msm_bits(&points[..n], &scalars, nbits),
+                    expected,
+                    "{}",
+                    msg
+                );
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn narrow_small() {
+        check_narrow_small::<bn256::G1Affine>();
+        check_narrow_small::<grumpkin::G1Affine>();
+        check_narrow_small::<pallas::Affine>();
+        check_narrow_small::<vesta::Affine>();
+    }
+
+    #[test]
+    fn narrow_matches_msm() {
+        check_narrow::<bn256::G1Affine>();
+        check_narrow::<grumpkin::G1Affine>();
+        check_narrow::<pallas::Affine>();
+        check_narrow::<vesta::Affine>();
+    }
+}
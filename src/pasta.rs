@@ -4,13 +4,19 @@
 #![allow(improper_ctypes)]
 #![allow(unused)]
 
+#[cfg(feature = "native")]
 extern crate semolina;
 
+use pasta_curves::arithmetic::CurveExt;
+use pasta_curves::group::Group;
 use pasta_curves::pallas;
 
 #[cfg(feature = "cuda")]
-use crate::{cuda, cuda_available, CUDA_OFF};
+use crate::cuda;
+use crate::ffi::{from_jacobian, Pippenger};
+use crate::{CurveId, Msm, MsmError};
 
+#[cfg(feature = "native")]
 extern "C" {
     fn mult_pippenger_pallas(
         out: *mut pallas::Point,
@@ -18,6 +24,36 @@ extern "C" {
         npoints: usize,
         scalars: *const pallas::Scalar,
         is_mont: bool,
+        nthreads: usize,
+    );
+
+    fn mult_pippenger_batch_pallas(
+        out: *mut pallas::Point,
+        points: *const pallas::Affine,
+        npoints: usize,
+        scalars: *const *const pallas::Scalar,
+        nbatches: usize,
+        nthreads: usize,
+    );
+
+    fn mult_pippenger_bits_pallas(
+        out: *mut pallas::Point,
+        points: *const pallas::Affine,
+        npoints: usize,
+        scalars: *const u8,
+        nbits: usize,
+        nthreads: usize,
+    );
+
+    fn mult_fixed_base_pallas(
+        out: *mut pallas::Point,
+        table: *const pallas::Affine,
+        npoints: usize,
+        ncopies: usize,
+        digits: *const i32,
+        window: usize,
+        nrounds: usize,
+        nthreads: usize,
     );
 }
 
@@ -25,11 +61,118 @@ pub fn pallas(
     points: &[pallas::Affine],
     scalars: &[pallas::Scalar],
 ) -> pallas::Point {
-    let npoints = points.len();
-    assert_eq!(npoints, scalars.len(), "length mismatch");
+    pallas::Affine::msm(points, scalars)
+}
+
+/// Like [`pallas()`], but reports errors instead of panicking.
+pub fn try_pallas(
+    points: &[pallas::Affine],
+    scalars: &[pallas::Scalar],
+) -> Result<pallas::Point, MsmError> {
+    pallas::Affine::try_msm(points, scalars)
+}
+
+#[cfg(feature = "native")]
+impl Pippenger for pallas::Affine {
+    unsafe fn mult_pippenger(
+        points: &[Self],
+        scalars: &[pallas::Scalar],
+        nthreads: usize,
+    ) -> Result<pallas::Point, MsmError> {
+        let mut ret = pallas::Point::default();
+        mult_pippenger_pallas(
+            &mut ret,
+            &points[0],
+            points.len(),
+            &scalars[0],
+            true,
+            nthreads,
+        );
+        from_jacobian(ret.jacobian_coordinates())
+    }
+
+    unsafe fn mult_pippenger_canonical(
+        points: &[Self],
+        scalars: &[[u64; 4]],
+        nthreads: usize,
+    ) -> Result<pallas::Point, MsmError> {
+        let mut ret = pallas::Point::default();
+        mult_pippenger_pallas(
+            &mut ret,
+            &points[0],
+            points.len(),
+            scalars.as_ptr() as *const pallas::Scalar,
+            false,
+            nthreads,
+        );
+        from_jacobian(ret.jacobian_coordinates())
+    }
+
+    unsafe fn mult_pippenger_bits(
+        points: &[Self],
+        scalars: &[u8],
+        nbits: usize,
+        nthreads: usize,
+    ) -> Result<pallas::Point, MsmError> {
+        let mut ret = pallas::Point::default();
+        mult_pippenger_bits_pallas(
+            &mut ret,
+            &points[0],
+            points.len(),
+            &scalars[0],
+            nbits,
+            nthreads,
+        );
+        from_jacobian(ret.jacobian_coordinates())
+    }
+
+    unsafe fn mult_pippenger_batch(
+        points: &[Self],
+        scalars: &[&[pallas::Scalar]],
+        nthreads: usize,
+    ) -> Result<Vec<pallas::Point>, MsmError> {
+        let mut ret = vec![pallas::Point::default(); scalars.len()];
+        let scalars = scalars.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+        mult_pippenger_batch_pallas(
+            ret.as_mut_ptr(),
+            points.as_ptr(),
+            points.len(),
+            scalars.as_ptr(),
+            scalars.len(),
+            nthreads,
+        );
+        ret.into_iter()
+            .map(|ret| from_jacobian(ret.jacobian_coordinates()))
+            .collect()
+    }
+
+    unsafe fn mult_fixed_base(
+        table: &[Self],
+        ncopies: usize,
+        digits: &[i32],
+        window: usize,
+        nrounds: usize,
+        nthreads: usize,
+    ) -> Result<pallas::Point, MsmError> {
+        let mut ret = pallas::Point::default();
+        mult_fixed_base_pallas(
+            &mut ret,
+            &table[0],
+            table.len() / ncopies,
+            ncopies,
+            &digits[0],
+            window,
+            nrounds,
+            nthreads,
+        );
+        from_jacobian(ret.jacobian_coordinates())
+    }
 
     #[cfg(feature = "cuda")]
-    if npoints >= 1 << 16 && unsafe { !CUDA_OFF && cuda_available() } {
+    unsafe fn cuda_pippenger(
+        points: &[Self],
+        scalars: &[pallas::Scalar],
+    ) -> Result<pallas::Point, MsmError> {
         extern "C" {
             fn cuda_pippenger_pallas(
                 out: *mut pallas::Point,
@@ -41,28 +184,31 @@ pub fn pallas(
 
         }
         let mut ret = pallas::Point::default();
-        let err = unsafe {
-            cuda_pippenger_pallas(
-                &mut ret,
-                &points[0],
-                npoints,
-                &scalars[0],
-                true,
-            )
-        };
-        assert!(err.code == 0, "{}", String::from(err));
-
-        return ret;
+        let err = cuda_pippenger_pallas(
+            &mut ret,
+            &points[0],
+            points.len(),
+            &scalars[0],
+            true,
+        );
+        if err.code != 0 {
+            return Err(err.into());
+        }
+
+        from_jacobian(ret.jacobian_coordinates())
     }
-    let mut ret = pallas::Point::default();
-    unsafe {
-        mult_pippenger_pallas(&mut ret, &points[0], npoints, &scalars[0], true)
-    };
-    ret
+}
+
+#[cfg(not(feature = "native"))]
+impl Pippenger for pallas::Affine {}
+
+impl Msm for pallas::Affine {
+    const CURVE_ID: CurveId = CurveId::Pallas;
 }
 
 use pasta_curves::vesta;
 
+#[cfg(feature = "native")]
 extern "C" {
     fn mult_pippenger_vesta(
         out: *mut vesta::Point,
@@ -70,6 +216,36 @@ extern "C" {
         npoints: usize,
         scalars: *const vesta::Scalar,
         is_mont: bool,
+        nthreads: usize,
+    );
+
+    fn mult_pippenger_batch_vesta(
+        out: *mut vesta::Point,
+        points: *const vesta::Affine,
+        npoints: usize,
+        scalars: *const *const vesta::Scalar,
+        nbatches: usize,
+        nthreads: usize,
+    );
+
+    fn mult_pippenger_bits_vesta(
+        out: *mut vesta::Point,
+        points: *const vesta::Affine,
+        npoints: usize,
+        scalars: *const u8,
+        nbits: usize,
+        nthreads: usize,
+    );
+
+    fn mult_fixed_base_vesta(
+        out: *mut vesta::Point,
+        table: *const vesta::Affine,
+        npoints: usize,
+        ncopies: usize,
+        digits: *const i32,
+        window: usize,
+        nrounds: usize,
+        nthreads: usize,
     );
 }
 
@@ -77,11 +253,118 @@ pub fn vesta(
     points: &[vesta::Affine],
     scalars: &[vesta::Scalar],
 ) -> vesta::Point {
-    let npoints = points.len();
-    assert_eq!(npoints, scalars.len(), "length mismatch");
+    vesta::Affine::msm(points, scalars)
+}
+
+/// Like [`vesta()`], but reports errors instead of panicking.
+pub fn try_vesta(
+    points: &[vesta::Affine],
+    scalars: &[vesta::Scalar],
+) -> Result<vesta::Point, MsmError> {
+    vesta::Affine::try_msm(points, scalars)
+}
+
+#[cfg(feature = "native")]
+impl Pippenger for vesta::Affine {
+    unsafe fn mult_pippenger(
+        points: &[Self],
+        scalars: &[vesta::Scalar],
+        nthreads: usize,
+    ) -> Result<vesta::Point, MsmError> {
+        let mut ret = vesta::Point::default();
+        mult_pippenger_vesta(
+            &mut ret,
+            &points[0],
+            points.len(),
+            &scalars[0],
+            true,
+            nthreads,
+        );
+        from_jacobian(ret.jacobian_coordinates())
+    }
+
+    unsafe fn mult_pippenger_canonical(
+        points: &[Self],
+        scalars: &[[u64; 4]],
+        nthreads: usize,
+    ) -> Result<vesta::Point, MsmError> {
+        let mut ret = vesta::Point::default();
+        mult_pippenger_vesta(
+            &mut ret,
+            &points[0],
+            points.len(),
+            scalars.as_ptr() as *const vesta::Scalar,
+            false,
+            nthreads,
+        );
+        from_jacobian(ret.jacobian_coordinates())
+    }
+
+    unsafe fn mult_pippenger_bits(
+        points: &[Self],
+        scalars: &[u8],
+        nbits: usize,
+        nthreads: usize,
+    ) -> Result<vesta::Point, MsmError> {
+        let mut ret = vesta::Point::default();
+        mult_pippenger_bits_vesta(
+            &mut ret,
+            &points[0],
+            points.len(),
+            &scalars[0],
+            nbits,
+            nthreads,
+        );
+        from_jacobian(ret.jacobian_coordinates())
+    }
+
+    unsafe fn mult_pippenger_batch(
+        points: &[Self],
+        scalars: &[&[vesta::Scalar]],
+        nthreads: usize,
+    ) -> Result<Vec<vesta::Point>, MsmError> {
+        let mut ret = vec![vesta::Point::default(); scalars.len()];
+        let scalars = scalars.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+        mult_pippenger_batch_vesta(
+            ret.as_mut_ptr(),
+            points.as_ptr(),
+            points.len(),
+            scalars.as_ptr(),
+            scalars.len(),
+            nthreads,
+        );
+        ret.into_iter()
+            .map(|ret| from_jacobian(ret.jacobian_coordinates()))
+            .collect()
+    }
+
+    unsafe fn mult_fixed_base(
+        table: &[Self],
+        ncopies: usize,
+        digits: &[i32],
+        window: usize,
+        nrounds: usize,
+        nthreads: usize,
+    ) -> Result<vesta::Point, MsmError> {
+        let mut ret = vesta::Point::default();
+        mult_fixed_base_vesta(
+            &mut ret,
+            &table[0],
+            table.len() / ncopies,
+            ncopies,
+            &digits[0],
+            window,
+            nrounds,
+            nthreads,
+        );
+        from_jacobian(ret.jacobian_coordinates())
+    }
 
     #[cfg(feature = "cuda")]
-    if npoints >= 1 << 16 && unsafe { !CUDA_OFF && cuda_available() } {
+    unsafe fn cuda_pippenger(
+        points: &[Self],
+        scalars: &[vesta::Scalar],
+    ) -> Result<vesta::Point, MsmError> {
         extern "C" {
             fn cuda_pippenger_vesta(
                 out: *mut vesta::Point,
@@ -93,162 +376,84 @@ pub fn vesta(
 
         }
         let mut ret = vesta::Point::default();
-        let err = unsafe {
-            cuda_pippenger_vesta(
-                &mut ret,
-                &points[0],
-                npoints,
-                &scalars[0],
-                true,
-            )
-        };
-        assert!(err.code == 0, "{}", String::from(err));
-
-        return ret;
+        let err = cuda_pippenger_vesta(
+            &mut ret,
+            &points[0],
+            points.len(),
+            &scalars[0],
+            true,
+        );
+        if err.code != 0 {
+            return Err(err.into());
+        }
+
+        from_jacobian(ret.jacobian_coordinates())
     }
-    let mut ret = vesta::Point::default();
-    unsafe {
-        mult_pippenger_vesta(&mut ret, &points[0], npoints, &scalars[0], true)
-    };
-    ret
 }
 
-pub mod utils {
-    use std::{
-        mem::transmute,
-        sync::{
-            atomic::{AtomicUsize, Ordering},
-            Arc, Mutex,
-        },
-    };
-
-    use pasta_curves::{
-        arithmetic::CurveExt,
-        group::{ff::Field, Curve},
-        pallas,
-    };
-    use rand::{RngCore, SeedableRng};
-    use rand_chacha::ChaCha20Rng;
-    use rayon::iter::{
-        IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator,
-    };
-
-    pub fn gen_points(npoints: usize) -> Vec<pallas::Affine> {
-        let ret = vec![pallas::Affine::default(); npoints];
-
-        let mut rnd = vec![0u8; 32 * npoints];
-        ChaCha20Rng::from_entropy().fill_bytes(&mut rnd);
-
-        let n_workers = rayon::current_num_threads();
-        let work = AtomicUsize::new(0);
-        rayon::scope(|s| {
-            for _ in 0..n_workers {
-                s.spawn(|_| {
-                    let hash = pallas::Point::hash_to_curve("foobar");
-
-                    let mut stride = 1024;
-                    let mut tmp = vec![pallas::Point::default(); stride];
-
-                    loop {
-                        let work = work.fetch_add(stride, Ordering::Relaxed);
-                        if work >= npoints {
-                            break;
-                        }
-                        if work + stride > npoints {
-                            stride = npoints - work;
-                            unsafe { tmp.set_len(stride) };
-                        }
-                        for (i, point) in
-                            tmp.iter_mut().enumerate().take(stride)
-                        {
-                            let off = (work + i) * 32;
-                            *point = hash(&rnd[off..off + 32]);
-                        }
-                        #[allow(mutable_transmutes)]
-                        pallas::Point::batch_normalize(&tmp, unsafe {
-                            transmute::<
-                                &[pallas::Affine],
-                                &mut [pallas::Affine],
-                            >(
-                                &ret[work..work + stride]
-                            )
-                        });
-                    }
-                })
-            }
-        });
-
-        ret
-    }
+#[cfg(not(feature = "native"))]
+impl Pippenger for vesta::Affine {}
 
-    pub fn gen_scalars(npoints: usize) -> Vec<pallas::Scalar> {
-        let ret =
-            Arc::new(Mutex::new(vec![pallas::Scalar::default(); npoints]));
-
-        let n_workers = rayon::current_num_threads();
-        let work = Arc::new(AtomicUsize::new(0));
-
-        rayon::scope(|s| {
-            for _ in 0..n_workers {
-                let ret_clone = Arc::clone(&ret);
-                let work_clone = Arc::clone(&work);
-
-                s.spawn(move |_| {
-                    let mut rng = ChaCha20Rng::from_entropy();
-                    loop {
-                        let work = work_clone.fetch_add(1, Ordering::Relaxed);
-                        if work >= npoints {
-                            break;
-                        }
-                        let mut ret = ret_clone.lock().unwrap();
-                        ret[work] = pallas::Scalar::random(&mut rng);
-                    }
-                });
-            }
-        });
-
-        Arc::try_unwrap(ret).unwrap().into_inner().unwrap()
-    }
+impl Msm for vesta::Affine {
+    const CURVE_ID: CurveId = CurveId::Vesta;
+}
 
-    pub fn naive_multiscalar_mul(
-        points: &[pallas::Affine],
-        scalars: &[pallas::Scalar],
-    ) -> pallas::Affine {
-        let ret: pallas::Point = points
-            .par_iter()
-            .zip_eq(scalars.par_iter())
-            .map(|(p, s)| p * s)
-            .sum();
-
-        ret.to_affine()
-    }
+/// The generic generators of [`crate::utils`], kept here for existing
+/// callers.
+pub mod utils {
+    pub use crate::utils::{
+        gen_edge_scalars, gen_points, gen_scalars, naive_multiscalar_mul,
+    };
 }
 
 #[cfg(test)]
 mod tests {
     use pasta_curves::group::Curve;
 
-    use crate::pasta::{
-        pallas,
-        utils::{gen_points, gen_scalars, naive_multiscalar_mul},
+    use crate::pasta::{pallas, vesta};
+    use crate::utils::{
+        gen_points_seeded, gen_scalars_seeded, naive_multiscalar_mul,
+        test_seed, TEST_SEED_VAR,
     };
+    use crate::Msm;
 
-    #[test]
-    fn it_works() {
+    fn check_it_works<C: Msm>(msm: fn(&[C], &[C::Scalar]) -> C::Curve) {
         #[cfg(not(debug_assertions))]
         const NPOINTS: usize = 128 * 1024;
         #[cfg(debug_assertions)]
         const NPOINTS: usize = 8 * 1024;
 
-        let points = gen_points(NPOINTS);
-        let scalars = gen_scalars(NPOINTS);
+        let seed = test_seed();
+        let points = gen_points_seeded::<C>(NPOINTS, seed);
+        let scalars = gen_scalars_seeded(NPOINTS, seed);
 
         let naive = naive_multiscalar_mul(&points, &scalars);
         println!("{:?}", naive);
 
-        let ret = pallas(&points, &scalars).to_affine();
+        let ret = msm(&points, &scalars).to_affine();
         println!("{:?}", ret);
 
-        assert_eq!(ret, naive);
+        assert_eq!(ret, naive, "{}={}", TEST_SEED_VAR, seed);
+    }
+
+    #[test]
+    fn it_works() {
+        check_it_works(pallas);
+        check_it_works(vesta);
+    }
+
+    #[test]
+    fn msm_trait_dispatch() {
+        let seed = test_seed();
+        let points = gen_points_seeded(100, seed);
+        let scalars = gen_scalars_seeded(100, seed);
+
+        assert_eq!(
+            pallas::Affine::msm(&points, &scalars),
+            pallas(&points, &scalars),
+            "{}={}",
+            TEST_SEED_VAR,
+            seed
+        );
     }
 }
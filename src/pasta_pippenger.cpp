@@ -7,20 +7,88 @@
 #include <ec/xyzz_t.hpp>
 #include <ff/pasta.hpp>
 
+#include "fixed_base.hpp"
+#include "pippenger_batch.hpp"
+#include "pippenger_bits.hpp"
+#include "thread_pools.hpp"
+
 static thread_pool_t da_pool;
 
 extern "C"
 void mult_pippenger_pallas(jacobian_t<pallas_t>& ret,
                            const xyzz_t<pallas_t>::affine_t points[],
-                           size_t npoints, const vesta_t scalars[], bool mont)
+                           size_t npoints, const vesta_t scalars[], bool mont,
+                           size_t nthreads)
 {   mult_pippenger<xyzz_t<pallas_t>>(ret, points, npoints, scalars, mont,
-                                     &da_pool);
+                                     select_pool(da_pool, nthreads));
 }
 
 extern "C"
 void mult_pippenger_vesta(jacobian_t<vesta_t>& ret,
                           const xyzz_t<vesta_t>::affine_t points[],
-                          size_t npoints, const pallas_t scalars[], bool mont)
+                          size_t npoints, const pallas_t scalars[], bool mont,
+                          size_t nthreads)
 {   mult_pippenger<xyzz_t<vesta_t>>(ret, points, npoints, scalars, mont,
-                                    &da_pool);
+                                    select_pool(da_pool, nthreads));
+}
+
+extern "C"
+void mult_pippenger_bits_pallas(jacobian_t<pallas_t>& ret,
+                                const xyzz_t<pallas_t>::affine_t points[],
+                                size_t npoints, const unsigned char scalars[],
+                                size_t nbits, size_t nthreads)
+{   mult_pippenger_bits<xyzz_t<pallas_t>>(ret, points, npoints, scalars,
+                                          nbits,
+                                          select_pool(da_pool, nthreads));
+}
+
+extern "C"
+void mult_pippenger_bits_vesta(jacobian_t<vesta_t>& ret,
+                               const xyzz_t<vesta_t>::affine_t points[],
+                               size_t npoints, const unsigned char scalars[],
+                               size_t nbits, size_t nthreads)
+{   mult_pippenger_bits<xyzz_t<vesta_t>>(ret, points, npoints, scalars,
+                                         nbits, select_pool(da_pool, nthreads));
+}
+
+extern "C"
+void mult_pippenger_batch_pallas(jacobian_t<pallas_t> ret[],
+                                 const xyzz_t<pallas_t>::affine_t points[],
+                                 size_t npoints, const vesta_t* const scalars[],
+                                 size_t nbatches, size_t nthreads)
+{   mult_pippenger_batch<xyzz_t<pallas_t>, pallas_t>(
+        ret, points, npoints, scalars, nbatches,
+        select_pool(da_pool, nthreads));
+}
+
+extern "C"
+void mult_pippenger_batch_vesta(jacobian_t<vesta_t> ret[],
+                                const xyzz_t<vesta_t>::affine_t points[],
+                                size_t npoints, const pallas_t* const scalars[],
+                                size_t nbatches, size_t nthreads)
+{   mult_pippenger_batch<xyzz_t<vesta_t>, vesta_t>(
+        ret, points, npoints, scalars, nbatches,
+        select_pool(da_pool, nthreads));
+}
+
+extern "C"
+void mult_fixed_base_pallas(jacobian_t<pallas_t>& ret,
+                            const xyzz_t<pallas_t>::affine_t table[],
+                            size_t npoints, size_t ncopies,
+                            const int digits[], size_t window,
+                            size_t nrounds, size_t nthreads)
+{   mult_fixed_base<xyzz_t<pallas_t>>(ret, table, npoints, ncopies,
+                                      digits, window, nrounds,
+                                      select_pool(da_pool, nthreads));
+}
+
+extern "C"
+void mult_fixed_base_vesta(jacobian_t<vesta_t>& ret,
+                           const xyzz_t<vesta_t>::affine_t table[],
+                           size_t npoints, size_t ncopies,
+                           const int digits[], size_t window,
+                           size_t nrounds, size_t nthreads)
+{   mult_fixed_base<xyzz_t<vesta_t>>(ret, table, npoints, ncopies,
+                                     digits, window, nrounds,
+                                     select_pool(da_pool, nthreads));
 }
@@ -0,0 +1,354 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Hiding Pedersen vector commitments `sum(v_i * G_i) + r * H` over the
+//! supported curves, computed with [`Msm`].
+
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use halo2curves::group::prime::PrimeCurveAffine;
+use halo2curves::group::{Curve, Group};
+use halo2curves::CurveAffine;
+
+use crate::generators::generators;
+use crate::{Msm, MsmError};
+
+/// Generators `G_i` for the committed values and `H` for the blinding
+/// factor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitmentKey<C: Msm> {
+    generators: Vec<C>,
+    blinding: C,
+}
+
+impl<C: Msm> CommitmentKey<C> {
+    /// Derives a key for up to `n` values from `label` with
+    /// [`generators`](crate::generators). `H` is the first point derived,
+    /// so that keys of any size for the same label agree.
+    pub fn setup(label: &[u8], n: usize) -> Self {
+        let mut generators = generators::<C>(label, n + 1);
+        let blinding = generators.remove(0);
+        Self {
+            generators,
+            blinding,
+        }
+    }
+
+    /// Wraps existing generators, e.g. of a [`crate::key::KeyFile`]. Nobody
+    /// may know discrete logarithms between any of them, which in particular
+    /// rules out `H` being the identity or one of the `G_i`, see
+    /// [`CommitmentKey::try_new`].
+    pub fn new(generators: Vec<C>, blinding: C) -> Self {
+        Self {
+            generators,
+            blinding,
+        }
+    }
+
+    /// Like [`CommitmentKey::new`], but fails with
+    /// [`MsmError::InvalidPoint`] if a generator is not on the curve, see
+    /// [`MsmConfig::validate`](crate::MsmConfig::validate). `H` counts as
+    /// the generator after the last `G_i`. Also fails with
+    /// [`MsmError::WeakBlinding`] if `H` is the identity or, up to sign,
+    /// one of the `G_i`, for which the blinding factor either vanishes or
+    /// merges with a value. Other relations between the generators cannot
+    /// be checked for.
+    pub fn try_new(generators: Vec<C>, blinding: C) -> Result<Self, MsmError> {
+        C::validate_points(&generators)?;
+        if !bool::from(blinding.is_on_curve()) {
+            return Err(MsmError::InvalidPoint {
+                index: generators.len(),
+            });
+        }
+        let negated = -blinding;
+        if bool::from(blinding.is_identity())
+            || generators.iter().any(|&g| g == blinding || g == negated)
+        {
+            return Err(MsmError::WeakBlinding);
+        }
+        Ok(Self::new(generators, blinding))
+    }
+
+    /// Largest number of values the key commits to.
+    pub fn len(&self) -> usize {
+        self.generators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.generators.is_empty()
+    }
+
+    pub fn generators(&self) -> &[C] {
+        &self.generators
+    }
+
+    pub fn blinding(&self) -> &C {
+        &self.blinding
+    }
+
+    /// Commits to `values` with blinding factor `blind`. There may be fewer
+    /// values than generators, the missing ones counting as zeros.
+    pub fn try_commit(
+        &self,
+        values: &[C::Scalar],
+        blind: &C::Scalar,
+    ) -> Result<Commitment<C>, MsmError> {
+        if values.len() > self.len() {
+            return Err(MsmError::LengthMismatch {
+                points: self.len(),
+                scalars: values.len(),
+            });
+        }
+        let point = C::try_msm(&self.generators[..values.len()], values)?;
+        Ok(Commitment {
+            point: point + self.blinding * blind,
+        })
+    }
+
+    pub fn commit(
+        &self,
+        values: &[C::Scalar],
+        blind: &C::Scalar,
+    ) -> Commitment<C> {
+        self.try_commit(values, blind)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Checks that `commitment` opens to `values` with `blind`.
+    pub fn verify(
+        &self,
+        commitment: &Commitment<C>,
+        values: &[C::Scalar],
+        blind: &C::Scalar,
+    ) -> bool {
+        self.try_commit(values, blind) == Ok(*commitment)
+    }
+}
+
+/// Pedersen commitment, additively homomorphic in both the values and the
+/// blinding factor: `commit(a, r) + commit(b, s) == commit(a + b, r + s)`
+/// and `commit(a, r) * k == commit(k * a, k * r)`.
+pub struct Commitment<C: Msm> {
+    point: C::Curve,
+}
+
+impl<C: Msm> Commitment<C> {
+    /// Commitment to nothing, with a zero blinding factor.
+    pub fn identity() -> Self {
+        Self {
+            point: C::Curve::identity(),
+        }
+    }
+
+    pub fn from_point(point: C::Curve) -> Self {
+        Self { point }
+    }
+
+    pub fn point(&self) -> C::Curve {
+        self.point
+    }
+
+    pub fn to_affine(&self) -> C {
+        self.point.to_affine()
+    }
+}
+
+impl<C: Msm> Clone for Commitment<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C: Msm> Copy for Commitment<C> {}
+
+impl<C: Msm> fmt::Debug for Commitment<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Commitment")
+            .field(&self.to_affine())
+            .finish()
+    }
+}
+
+impl<C: Msm> PartialEq for Commitment<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.point == other.point
+    }
+}
+
+impl<C: Msm> Eq for Commitment<C> {}
+
+impl<C: Msm> Add for Commitment<C> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            point: self.point + other.point,
+        }
+    }
+}
+
+impl<C: Msm> AddAssign for Commitment<C> {
+    fn add_assign(&mut self, other: Self) {
+        self.point += other.point;
+    }
+}
+
+impl<C: Msm> Sub for Commitment<C> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            point: self.point - other.point,
+        }
+    }
+}
+
+impl<C: Msm> SubAssign for Commitment<C> {
+    fn sub_assign(&mut self, other: Self) {
+        self.point -= other.point;
+    }
+}
+
+impl<C: Msm> Neg for Commitment<C> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { point: -self.point }
+    }
+}
+
+impl<C: Msm> Mul<C::Scalar> for Commitment<C> {
+    type Output = Self;
+
+    fn mul(self, scalar: C::Scalar) -> Self {
+        Self {
+            point: self.point * scalar,
+        }
+    }
+}
+
+impl<C: Msm> MulAssign<C::Scalar> for Commitment<C> {
+    fn mul_assign(&mut self, scalar: C::Scalar) {
+        self.point *= scalar;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256;
+    use halo2curves::ff::Field;
+    use halo2curves::grumpkin;
+    use pasta_curves::{pallas, vesta};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    use super::*;
+    use crate::utils::{gen_scalars_seeded, off_curve};
+
+    fn check_pedersen<C: Msm>() {
+        let mut rng = ChaCha20Rng::seed_from_u64(24);
+        let ck = CommitmentKey::<C>::setup(b"pedersen test", 100);
+        assert_eq!(ck.len(), 100);
+        let gens = generators::<C>(b"pedersen test", 101);
+        assert_eq!(ck.blinding(), &gens[0]);
+        assert_eq!(ck.generators(), &gens[1..]);
+        assert_eq!(
+            CommitmentKey::<C>::setup(b"pedersen test", 10).blinding(),
+            &gens[0]
+        );
+
+        let a = gen_scalars_seeded::<C::Scalar>(100, 24);
+        let b = gen_scalars_seeded::<C::Scalar>(60, 25);
+        let (r, s, k) = (
+            C::Scalar::random(&mut rng),
+            C::Scalar::random(&mut rng),
+            C::Scalar::random(&mut rng),
+        );
+
+        let ca = ck.commit(&a, &r);
+        let cb = ck.commit(&b, &s);
+        assert_eq!(
+            ca.point(),
+            C::msm(ck.generators(), &a) + *ck.blinding() * r
+        );
+        assert!(ck.verify(&ca, &a, &r));
+        assert!(!ck.verify(&ca, &a, &s));
+        assert!(!ck.verify(&ca, &a[..99], &r));
+        let mut tampered = a.clone();
+        tampered[42] += C::Scalar::ONE;
+        assert!(!ck.verify(&ca, &tampered, &r));
+
+        // Hiding: the same values under different blinding factors.
+        assert_ne!(ck.commit(&a, &r), ck.commit(&a, &s));
+        // Trailing zeros do not change the commitment.
+        let mut padded = b.clone();
+        padded.resize(100, C::Scalar::ZERO);
+        assert_eq!(ck.commit(&padded, &s), cb);
+
+        let sum = a
+            .iter()
+            .zip(&padded)
+            .map(|(a, b)| *a + b)
+            .collect::<Vec<_>>();
+        assert_eq!(ca + cb, ck.commit(&sum, &(r + s)));
+        let diff = a
+            .iter()
+            .zip(&padded)
+            .map(|(a, b)| *a - b)
+            .collect::<Vec<_>>();
+        assert_eq!(ca - cb, ck.commit(&diff, &(r - s)));
+        let scaled = a.iter().map(|a| *a * k).collect::<Vec<_>>();
+        assert_eq!(ca * k, ck.commit(&scaled, &(r * k)));
+        assert_eq!(-ca + ca, Commitment::identity());
+        assert_eq!(ck.commit(&[], &C::Scalar::ZERO), Commitment::identity());
+
+        let mut acc = ca;
+        acc += cb;
+        acc -= ca;
+        acc *= k;
+        assert_eq!(acc, cb * k);
+
+        assert_eq!(
+            ck.try_commit(&gen_scalars_seeded(101, 26), &r),
+            Err(MsmError::LengthMismatch {
+                points: 100,
+                scalars: 101
+            })
+        );
+
+        let key = || (ck.generators().to_vec(), *ck.blinding());
+        assert_eq!(CommitmentKey::try_new(key().0, key().1), Ok(ck.clone()));
+        let (mut bad, blinding) = key();
+        bad[37] = off_curve(bad[37]);
+        assert_eq!(
+            CommitmentKey::try_new(bad, blinding),
+            Err(MsmError::InvalidPoint { index: 37 })
+        );
+        let (generators, blinding) = key();
+        assert_eq!(
+            CommitmentKey::try_new(generators, off_curve(blinding)),
+            Err(MsmError::InvalidPoint { index: 100 })
+        );
+        let (generators, _) = key();
+        assert_eq!(
+            CommitmentKey::try_new(generators, C::identity()),
+            Err(MsmError::WeakBlinding)
+        );
+        for blinding in [key().0[63], -key().0[0], key().0[99]] {
+            assert_eq!(
+                CommitmentKey::try_new(key().0, blinding),
+                Err(MsmError::WeakBlinding)
+            );
+        }
+    }
+
+    #[test]
+    fn pedersen_commitments() {
+        check_pedersen::<bn256::G1Affine>();
+        check_pedersen::<grumpkin::G1Affine>();
+        check_pedersen::<pallas::Affine>();
+        check_pedersen::<vesta::Affine>();
+    }
+}
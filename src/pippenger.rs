@@ -0,0 +1,395 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(feature = "pure-rust")]
+use std::collections::BTreeMap;
+#[cfg(feature = "pure-rust")]
+use std::sync::{Arc, Mutex};
+
+use halo2curves::ff::PrimeField;
+use halo2curves::group::Group;
+use halo2curves::CurveAffine;
+use rayon::prelude::*;
+
+/// Rust counterpart of the C++ `select_pool`, running `msm` on the threads
+/// asked for by `nthreads`: the calling thread alone for 1, the current
+/// rayon pool for 0 or at least as many as there are CPUs, and otherwise a
+/// pool of exactly `nthreads` threads, created on first use and shared by
+/// all later calls asking for the same count. `msm` is told whether it may
+/// spread its work over the pool.
+#[cfg(feature = "pure-rust")]
+pub(crate) fn with_threads<R: Send>(
+    nthreads: usize,
+    msm: impl FnOnce(bool) -> R + Send,
+) -> R {
+    static POOLS: Mutex<BTreeMap<usize, Arc<rayon::ThreadPool>>> =
+        Mutex::new(BTreeMap::new());
+
+    let ncpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if nthreads == 1 {
+        return msm(false);
+    }
+    if nthreads == 0 || nthreads >= ncpus {
+        return msm(true);
+    }
+    let pool = POOLS
+        .lock()
+        .unwrap()
+        .entry(nthreads)
+        .or_insert_with(|| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(nthreads);
+            Arc::new(pool.build().expect("failed to start MSM threads"))
+        })
+        .clone();
+    pool.install(|| msm(true))
+}
+
+/// Pippenger window width for `npoints` points, same as picked by the C++
+/// implementation.
+pub(crate) fn window_size(npoints: usize) -> usize {
+    let log =
+        usize::BITS as usize - 1 - npoints.max(1).leading_zeros() as usize;
+    match log {
+        0 => 1,
+        1..=4 => 2,
+        5..=12 => log - 2,
+        _ => log - 3,
+    }
+}
+
+/// Rust counterpart of the C++ `mult_pippenger`, used by the `pure-rust`
+/// backend. Windows are summed up on the current rayon pool if `parallel`
+/// is set and on the calling thread otherwise.
+pub(crate) fn msm<C: CurveAffine>(
+    points: &[C],
+    scalars: &[C::Scalar],
+    parallel: bool,
+) -> C::Curve {
+    let len = <C::Scalar as PrimeField>::Repr::default().as_ref().len();
+    let mut bytes = vec![0u8; scalars.len() * len];
+    bytes.par_chunks_mut(len).zip(scalars.par_iter()).for_each(
+        |(bytes, scalar)| bytes.copy_from_slice(scalar.to_repr().as_ref()),
+    );
+
+    msm_bytes(points, &bytes, C::Scalar::NUM_BITS as usize, parallel)
+}
+
+/// Rust counterpart of the C++ `mult_pippenger_bits`, taking scalars as
+/// little-endian byte strings of `(nbits + 7) / 8` bytes each. Bits above
+/// `nbits` are ignored.
+pub(crate) fn msm_bytes<C: CurveAffine>(
+    points: &[C],
+    scalars: &[u8],
+    nbits: usize,
+    parallel: bool,
+) -> C::Curve {
+    let nbytes = (nbits + 7) / 8;
+    let window = window_size(points.len()).min(nbits.max(1));
+    let nwindows = (nbits + window - 1) / window;
+
+    let window_sum = |w: usize| {
+        let bit = w * window;
+        let width = window.min(nbits - bit);
+        let mut buckets = vec![C::Curve::identity(); (1 << width) - 1];
+        for (point, scalar) in points.iter().zip(scalars.chunks(nbytes)) {
+            let digit = get_bits(scalar, bit, width);
+            if digit != 0 {
+                buckets[digit - 1] += point;
+            }
+        }
+
+        // Bucket i holds the points with digit i + 1, so a running sum from
+        // the top adds each bucket in as many times as its digit.
+        let mut running = C::Curve::identity();
+        let mut sum = C::Curve::identity();
+        for bucket in buckets.into_iter().rev() {
+            running += bucket;
+            sum += running;
+        }
+        sum
+    };
+    let sums = if parallel {
+        (0..nwindows)
+            .into_par_iter()
+            .map(window_sum)
+            .collect::<Vec<_>>()
+    } else {
+        (0..nwindows).map(window_sum).collect::<Vec<_>>()
+    };
+
+    sums.into_iter()
+        .rev()
+        .fold(C::Curve::identity(), |mut acc, sum| {
+            for _ in 0..window {
+                acc = acc.double();
+            }
+            acc + sum
+        })
+}
+
+/// Rust counterpart of the C++ `mult_pippenger_batch`, computing the MSM
+/// of `points` with every vector of `scalars` while loading each point once
+/// per window. Windows are summed up on the current rayon pool if
+/// `parallel` is set and on the calling thread otherwise.
+pub(crate) fn msm_batch<C: CurveAffine>(
+    points: &[C],
+    scalars: &[&[C::Scalar]],
+    parallel: bool,
+) -> Vec<C::Curve> {
+    let nbatches = scalars.len();
+    let nbits = C::Scalar::NUM_BITS as usize;
+    let len = <C::Scalar as PrimeField>::Repr::default().as_ref().len();
+
+    // Point-major, so that the digits of a point across the batch are
+    // adjacent: bytes[(i * nbatches + k) * len..] is scalars[k][i].
+    let mut bytes = vec![0u8; points.len() * nbatches * len];
+    bytes
+        .par_chunks_mut(len)
+        .enumerate()
+        .for_each(|(j, bytes)| {
+            let scalar = scalars[j % nbatches][j / nbatches];
+            bytes.copy_from_slice(scalar.to_repr().as_ref())
+        });
+
+    let window = window_size(points.len());
+    let nwindows = (nbits + window - 1) / window;
+    let window_sums = |w: usize| {
+        let bit = w * window;
+        let width = window.min(nbits - bit);
+        let nbuckets = (1 << width) - 1;
+        let mut buckets = vec![C::Curve::identity(); nbuckets * nbatches];
+        for (point, scalars) in points.iter().zip(bytes.chunks(nbatches * len))
+        {
+            for (buckets, scalar) in
+                buckets.chunks_mut(nbuckets).zip(scalars.chunks(len))
+            {
+                let digit = get_bits(scalar, bit, width);
+                if digit != 0 {
+                    buckets[digit - 1] += point;
+                }
+            }
+        }
+
+        buckets
+            .chunks(nbuckets)
+            .map(|buckets| {
+                let mut running = C::Curve::identity();
+                let mut sum = C::Curve::identity();
+                for bucket in buckets.iter().rev() {
+                    running += bucket;
+                    sum += running;
+                }
+                sum
+            })
+            .collect::<Vec<_>>()
+    };
+    let sums = if parallel {
+        (0..nwindows)
+            .into_par_iter()
+            .map(window_sums)
+            .collect::<Vec<_>>()
+    } else {
+        (0..nwindows).map(window_sums).collect::<Vec<_>>()
+    };
+
+    sums.into_iter().rev().fold(
+        vec![C::Curve::identity(); nbatches],
+        |mut acc, sums| {
+            for (acc, sum) in acc.iter_mut().zip(sums) {
+                for _ in 0..window {
+                    *acc = acc.double();
+                }
+                *acc += sum;
+            }
+            acc
+        },
+    )
+}
+
+/// Rust counterpart of the C++ `mult_fixed_base`. The bucket range is split
+/// over the current rayon pool if `parallel` is set.
+pub(crate) fn fixed_base<C: CurveAffine>(
+    table: &[C],
+    ncopies: usize,
+    digits: &[i32],
+    window: usize,
+    nrounds: usize,
+    parallel: bool,
+) -> C::Curve {
+    let nbuckets = 1 << (window - 1);
+    let ndigits = ncopies * nrounds;
+    let nparts = match parallel && table.len() >= 1024 {
+        true => rayon::current_num_threads().min(nbuckets),
+        false => 1,
+    };
+
+    let part_sum = |part: usize| {
+        // Bucket b collects the digits of magnitude lo + b + 1.
+        let lo = nbuckets * part / nparts;
+        let hi = nbuckets * (part + 1) / nparts;
+        let mut acc = C::Curve::identity();
+        for q in (0..nrounds).rev() {
+            for _ in 0..window {
+                acc = acc.double();
+            }
+            let mut buckets = vec![C::Curve::identity(); hi - lo];
+            for (points, digits) in
+                table.chunks(ncopies).zip(digits.chunks(ndigits))
+            {
+                for (point, digit) in
+                    points.iter().zip(digits[q..].iter().step_by(nrounds))
+                {
+                    let b =
+                        (digit.unsigned_abs() as usize).wrapping_sub(lo + 1);
+                    if b < hi - lo {
+                        match *digit < 0 {
+                            true => buckets[b] -= point,
+                            false => buckets[b] += point,
+                        }
+                    }
+                }
+            }
+
+            let mut running = C::Curve::identity();
+            let mut sum = C::Curve::identity();
+            for bucket in buckets.into_iter().rev() {
+                running += bucket;
+                sum += running;
+            }
+            acc += sum + running * C::Scalar::from(lo as u64);
+        }
+        acc
+    };
+
+    if parallel {
+        (0..nparts).into_par_iter().map(part_sum).sum()
+    } else {
+        (0..nparts).map(part_sum).sum()
+    }
+}
+
+/// Extracts `width` bits starting at bit `bit` of a little-endian byte
+/// string, reading zeros past its end.
+pub(crate) fn get_bits(bytes: &[u8], bit: usize, width: usize) -> usize {
+    let mut buf = [0u8; 8];
+    let start = (bit / 8).min(bytes.len());
+    let end = bytes.len().min(start + 8);
+    buf[..end - start].copy_from_slice(&bytes[start..end]);
+    let word = u64::from_le_bytes(buf) >> (bit % 8);
+    (word & ((1 << width) - 1)) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256;
+    use halo2curves::grumpkin;
+    use pasta_curves::{pallas, vesta};
+    use rand::{RngCore, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    use super::*;
+    use crate::utils::{
+        gen_points_seeded, gen_scalars_seeded, test_seed, TEST_SEED_VAR,
+    };
+    use crate::{small, Msm};
+
+    fn check_pippenger<C: Msm>() {
+        let seed = test_seed();
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        for n in [1, 2, 9, 100, 1000] {
+            let points = gen_points_seeded::<C>(n, seed);
+            let scalars = gen_scalars_seeded::<C::Scalar>(n, seed);
+            let msg = format!("n = {}, {}={}", n, TEST_SEED_VAR, seed);
+
+            let expected = small::msm(&points, &scalars);
+            assert_eq!(msm(&points, &scalars, false), expected, "{}", msg);
+            assert_eq!(msm(&points, &scalars, true), expected, "{}", msg);
+            let negated = scalars.iter().map(|s| -*s).collect::<Vec<_>>();
+            for parallel in [false, true] {
+                assert_eq!(
+                    msm_batch(&points, &[&scalars, &negated], parallel),
+                    vec![expected, -expected],
+                    "{}",
+                    msg
+                );
+            }
+            #[cfg(feature = "native")]
+            assert_eq!(
+                unsafe { C::mult_pippenger(&points, &scalars, 0) },
+                Ok(expected),
+                "{}",
+                msg
+            );
+
+            for nbits in [1, 7, 8, 13, 64] {
+                let nbytes = (nbits + 7) / 8;
+                let mut bytes = vec![0u8; n * nbytes];
+                rng.fill_bytes(&mut bytes);
+                let ret = msm_bytes(&points, &bytes, nbits, true);
+                #[cfg(feature = "native")]
+                assert_eq!(
+                    unsafe {
+                        C::mult_pippenger_bits(&points, &bytes, nbits, 0)
+                    },
+                    Ok(ret),
+                    "{}, nbits = {}",
+                    msg,
+                    nbits
+                );
+
+                let scalars = bytes
+                    .chunks(nbytes)
+                    .map(|bytes| {
+                        let mut repr =
+                            <C::Scalar as PrimeField>::Repr::default();
+                        repr.as_mut()[..nbytes].copy_from_slice(bytes);
+                        repr.as_mut()[nbytes - 1] &=
+                            0xff >> (8 * nbytes - nbits);
+                        C::Scalar::from_repr(repr).unwrap()
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    ret,
+                    small::msm(&points, &scalars),
+                    "{}, nbits = {}",
+                    msg,
+                    nbits
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "pure-rust")]
+    #[test]
+    fn thread_counts() {
+        use std::collections::HashSet;
+        use std::thread;
+        use std::time::Duration;
+
+        let ncpus = thread::available_parallelism().map_or(1, |n| n.get());
+        for nthreads in [1, 2, 3] {
+            let threads = with_threads(nthreads, |parallel| {
+                assert_eq!(parallel, nthreads != 1);
+                assert!(rayon::current_num_threads() <= nthreads.max(ncpus));
+                (0..64)
+                    .into_par_iter()
+                    .map(|_| {
+                        thread::sleep(Duration::from_millis(1));
+                        thread::current().id()
+                    })
+                    .collect::<HashSet<_>>()
+            });
+            let max = if nthreads == 1 { 1 } else { nthreads };
+            assert!(threads.len() <= max, "nthreads = {}", nthreads);
+        }
+    }
+
+    #[test]
+    fn pippenger_matches_reference() {
+        check_pippenger::<bn256::G1Affine>();
+        check_pippenger::<grumpkin::G1Affine>();
+        check_pippenger::<pallas::Affine>();
+        check_pippenger::<vesta::Affine>();
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+#ifndef __GRUMPKIN_MSM_PIPPENGER_BATCH_HPP__
+#define __GRUMPKIN_MSM_PIPPENGER_BATCH_HPP__
+
+#include <msm/pippenger.hpp>
+
+/* Affine point with accessible coordinates, laid out as sppark's Affine_t */
+template <class field_t>
+struct xy_t {
+    field_t X, Y;
+
+    bool is_inf() const { return X.is_zero() & Y.is_zero(); }
+    /* field_t's default constructor leaves it uninitialized */
+    void inf()          { X.zero(); Y.zero(); }
+};
+
+/*
+ * Buckets kept in affine form. Additions are queued and carried out in
+ * groups of up to N, sharing a single inversion by Montgomery's trick, at
+ * 6M+1S apiece instead of the 8M+2S of an XYZZ mixed addition. A bucket
+ * also takes half the memory of an XYZZ one. Assumes a curve with a=0.
+ */
+template <class field_t, size_t N = 256>
+class affine_buckets_t {
+    std::vector<xy_t<field_t>> buckets; /* zeroed, i.e. at infinity */
+    std::vector<unsigned char> queued;
+    size_t idx[N], n = 0;
+    const xy_t<field_t>* pts[N];
+    field_t den[N], acc[N];
+
+public:
+    affine_buckets_t(size_t nbuckets) : buckets(nbuckets), queued(nbuckets) {}
+
+    xy_t<field_t>& operator[](size_t b) { return buckets[b]; }
+
+    void add(size_t b, const xy_t<field_t>& p)
+    {
+        if (p.is_inf())
+            return;
+        if (queued[b])
+            flush();
+
+        xy_t<field_t>& bucket = buckets[b];
+        if (bucket.is_inf()) {
+            bucket = p;
+            return;
+        }
+
+        field_t d = p.X - bucket.X;
+        if (d.is_zero()) {
+            if (!(p.Y - bucket.Y).is_zero()) {  /* opposite points */
+                bucket.inf();
+                return;
+            }
+            d = p.Y + p.Y;                      /* doubling */
+        }
+
+        idx[n] = b;
+        pts[n] = &p;
+        den[n] = d;
+        queued[b] = 1;
+        if (++n == N)
+            flush();
+    }
+
+    void flush()
+    {
+        if (n == 0)
+            return;
+
+        acc[0] = den[0];
+        for (size_t j = 1; j < n; j++)
+            acc[j] = acc[j - 1] * den[j];
+        field_t inv = acc[n - 1].reciprocal();
+
+        for (size_t j = n; j--;) {
+            field_t lambda;
+            if (j) {
+                lambda = inv * acc[j - 1];
+                inv *= den[j];
+            } else {
+                lambda = inv;
+            }
+
+            xy_t<field_t>& bucket = buckets[idx[j]];
+            const xy_t<field_t>& p = *pts[j];
+            if ((p.X - bucket.X).is_zero()) {  /* lambda = 3*X^2 / 2*Y */
+                field_t xx = p.X * p.X;
+                lambda *= xx + xx + xx;
+            } else {                            /* lambda = dY / dX */
+                lambda *= p.Y - bucket.Y;
+            }
+
+            field_t x = lambda * lambda - bucket.X - p.X;
+            bucket.Y = lambda * (bucket.X - x) - bucket.Y;
+            bucket.X = x;
+            queued[idx[j]] = 0;
+        }
+        n = 0;
+    }
+};
+
+/*
+ * Batch variant of sppark's mult_pippenger(), computing ret[k] as the MSM
+ * of |points| with scalars[k] for every k < nbatches. The scalars are
+ * converted out of Montgomery form once into a point-major layout, and
+ * every tile, i.e. a range of points in one window, loads each of its
+ * points once and adds it to the buckets of all vectors in the same pass.
+ * The buckets are affine, see affine_buckets_t, which is affordable since
+ * the batch supplies enough independent additions to amortize the
+ * inversions. Tiles are spread over |da_pool|, splitting the points as
+ * well as the windows if there are more threads than windows.
+ */
+template <class bucket_t, class field_t, class point_t, class scalar_t,
+          class affine_t = class bucket_t::affine_t>
+static void mult_pippenger_batch(point_t ret[], const affine_t _points[],
+                                 size_t npoints,
+                                 const scalar_t* const _scalars[],
+                                 size_t nbatches,
+                                 thread_pool_t* da_pool = nullptr)
+{
+    typedef typename scalar_t::pow_t pow_t;
+    static_assert(sizeof(affine_t) == sizeof(xy_t<field_t>),
+                  "affine_t is not a pair of coordinates");
+    const xy_t<field_t>* points =
+        reinterpret_cast<const xy_t<field_t>*>(_points);
+    size_t nbits = scalar_t::nbits;
+    size_t ncpus = da_pool ? da_pool->size() : 0;
+
+    if (nbatches == 0)
+        return;
+
+    /* scalars[i*nbatches + k] is the scalar of point i in vector k */
+    std::unique_ptr<pow_t[]> scalars(new pow_t[npoints * nbatches]);
+    auto convert = [&](size_t i) {
+        for (size_t k = 0; k < nbatches; k++)
+            _scalars[k][i].to_scalar(scalars[i * nbatches + k]);
+    };
+    if (ncpus < 2 || npoints < 1024) {
+        for (size_t i = 0; i < npoints; i++)
+            convert(i);
+    } else {
+        da_pool->par_map(npoints, 512, convert);
+    }
+
+    size_t window = window_size(npoints),
+           ny = (nbits + window - 1) / window,
+           nx = 1;
+    if (ncpus > ny)
+        nx = std::max(std::min(ncpus / ny, npoints / 32), (size_t)1);
+    size_t total = nx * ny;
+
+    /* tiles[(y*nx + x)*nbatches + k] is vector k's sum of tile (x, y) */
+    std::vector<point_t> tiles(total * nbatches);
+    auto run = [&](size_t work) {
+        size_t x = work % nx,
+               bit0 = work / nx * window,
+               wbits = std::min(window, nbits - bit0),
+               nbuckets = ((size_t)1 << wbits) - 1;
+        /* bucket k*nbuckets + d-1 collects the points of digit d in k */
+        std::unique_ptr<affine_buckets_t<field_t>> buckets(
+            new affine_buckets_t<field_t>(nbuckets * nbatches));
+
+        for (size_t i = npoints * x / nx; i < npoints * (x + 1) / nx; i++) {
+            const pow_t* s = &scalars[i * nbatches];
+            for (size_t k = 0; k < nbatches; k++) {
+                size_t digit = get_wval(s[k], bit0, wbits) & nbuckets;
+                if (digit)
+                    buckets->add(k * nbuckets + digit - 1, points[i]);
+            }
+        }
+        buckets->flush();
+
+        for (size_t k = 0; k < nbatches; k++) {
+            bucket_t acc, sum;
+            acc.inf();
+            sum.inf();
+            for (size_t b = nbuckets; b--;) {
+                const xy_t<field_t>& p = (*buckets)[k * nbuckets + b];
+                acc.add(affine_t(p.X, p.Y));
+                sum.add(acc);
+            }
+            tiles[work * nbatches + k] = sum;
+        }
+    };
+
+    if (ncpus < 2 || npoints < 32) {
+        for (size_t work = 0; work < total; work++)
+            run(work);
+    } else {
+        da_pool->par_map(total, run);
+    }
+
+    for (size_t k = 0; k < nbatches; k++) {
+        ret[k].inf();
+        for (size_t y = ny; y--;) {
+            for (size_t x = 0; x < nx; x++)
+                ret[k].add(tiles[(y * nx + x) * nbatches + k]);
+            if (y == 0)
+                break;
+            for (size_t i = 0; i < window; i++)
+                ret[k].dbl();
+        }
+    }
+}
+
+#endif
@@ -0,0 +1,173 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+#ifndef __GRUMPKIN_MSM_PIPPENGER_BITS_HPP__
+#define __GRUMPKIN_MSM_PIPPENGER_BITS_HPP__
+
+#include <msm/pippenger.hpp>
+
+/*
+ * Same as sppark's breakdown(), but safe for narrow scalars, where the
+ * original can run the window down to zero or hand out empty columns.
+ */
+std::tuple<size_t, size_t, size_t>
+static breakdown_bits(size_t nbits, size_t window, size_t npoints,
+                      size_t ncpus)
+{
+    size_t nx, ny, wnd;
+
+    if (window > nbits)
+        window = nbits;
+
+    if (nbits > window * ncpus) {
+        std::tie(nx, ny, wnd) = breakdown(nbits, window, ncpus);
+    } else {
+        nx = 1;
+        wnd = window;
+        while ((nbits / wnd + 1) * nx < ncpus && (nx + 1) * 32 <= npoints) {
+            nx += 1;
+            size_t shrink = num_bits(3 * nx / 2);
+            wnd = window > shrink ? window - shrink : 1;
+        }
+        ny = nbits / wnd + 1;
+        wnd = nbits / ny + 1;
+    }
+
+    /*
+     * With |nbits| a multiple of |wnd| the row count above leaves the top
+     * row empty, at bit |nbits|, where tile() would read a byte past the
+     * last scalar. Recount the rows to cover exactly |nbits| bits.
+     */
+    ny = (nbits + wnd - 1) / wnd;
+
+    return std::make_tuple(nx, ny, wnd);
+}
+
+/*
+ * Variant of sppark's mult_pippenger() taking scalars as canonical
+ * little-endian byte strings of (nbits+7)/8 bytes each. The number of
+ * windows is proportional to |nbits| rather than to the field width.
+ */
+template <class bucket_t, class point_t,
+          class affine_t = class bucket_t::affine_t>
+static void mult_pippenger_bits(point_t& ret, const affine_t points[],
+                                size_t npoints, const unsigned char scalars[],
+                                size_t nbits, thread_pool_t* da_pool = nullptr)
+{
+    size_t nbytes = (nbits + 7) / 8;
+    size_t window = window_size(npoints);
+    size_t ncpus = da_pool ? da_pool->size() : 0;
+
+    if (ncpus < 2 || npoints < 32) {
+        if (npoints == 1) {
+            mult(ret, points[0], scalars, nbits);
+            return;
+        }
+
+        std::vector<bucket_t> buckets(1 << window); /* zeroed */
+
+        point_t p;
+        ret.inf();
+
+        /*
+         * top excess bits modulo target window size, a whole window if
+         * none, as an empty window at bit |nbits| would read a byte past
+         * the last scalar
+         */
+        size_t wbits = (nbits - 1) % window + 1,
+               cbits = wbits + (wbits < window),
+               bit0 = nbits;
+        while (bit0 -= wbits) {
+            tile(p, points, npoints, scalars, nbits,
+                    &buckets[0], bit0, wbits, cbits);
+            ret.add(p);
+            for (size_t i = 0; i < window; i++)
+                ret.dbl();
+            cbits = wbits = window;
+        }
+        tile(p, points, npoints, scalars, nbits,
+                &buckets[0], 0, wbits, cbits);
+        ret.add(p);
+        return;
+    }
+
+    size_t nx, ny;
+    std::tie(nx, ny, window) = breakdown_bits(nbits, window, npoints, ncpus);
+
+    struct tile_t {
+        size_t x, dx, y, dy;
+        point_t p;
+        tile_t() {}
+    };
+    std::vector<tile_t> grid(nx * ny);
+
+    size_t dx = npoints / nx,
+           y  = window * (ny - 1);
+
+    size_t total = 0;
+    while (total < nx) {
+        grid[total].x  = total * dx;
+        grid[total].dx = dx;
+        grid[total].y  = y;
+        grid[total].dy = nbits - y;
+        total++;
+    }
+    grid[total - 1].dx = npoints - grid[total - 1].x;
+
+    while (y) {
+        y -= window;
+        for (size_t i = 0; i < nx; i++, total++) {
+            grid[total].x  = grid[i].x;
+            grid[total].dx = grid[i].dx;
+            grid[total].y  = y;
+            grid[total].dy = window;
+        }
+    }
+
+    std::vector<std::atomic<size_t>> row_sync(ny); /* zeroed */
+    counter_t<size_t> counter(0);
+    channel_t<size_t> ch;
+
+    auto n_workers = std::min(ncpus, total);
+    while (n_workers--) {
+        da_pool->spawn([&, window, total, nbits, nbytes, counter]() {
+            size_t work;
+            if ((work = counter++) < total) {
+                std::vector<bucket_t> buckets(1 << window); /* zeroed */
+
+                do {
+                    size_t x  = grid[work].x,
+                           dx = grid[work].dx,
+                           y  = grid[work].y,
+                           dy = grid[work].dy;
+                    tile(grid[work].p, &points[x], dx,
+                                       &scalars[x * nbytes], nbits,
+                                       &buckets[0], y, dy, dy + (dy < window));
+                    if (++row_sync[y / window] == nx)
+                        ch.send(y);
+                } while ((work = counter++) < total);
+            }
+        });
+    }
+
+    ret.inf();
+    size_t row = 0;
+    while (ny--) {
+        auto y = ch.recv();
+        row_sync[y / window] = -1U;
+        while (grid[row].y == y) {
+            while (row < total && grid[row].y == y)
+                ret.add(grid[row++].p);
+            if (y == 0)
+                break;
+            for (size_t i = 0; i < window; i++)
+                ret.dbl();
+            y -= window;
+            if (row_sync[y / window] != -1U)
+                break;
+        }
+    }
+}
+
+#endif
@@ -0,0 +1,80 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use halo2curves::group::{Curve, Group};
+use halo2curves::CurveAffine;
+use rayon::prelude::*;
+
+use crate::config::MsmConfig;
+use crate::error::check_lengths;
+use crate::{Msm, MsmError};
+
+pub(crate) fn try_msm<C: Msm>(
+    points: &[C::Curve],
+    scalars: &[C::Scalar],
+    config: &MsmConfig,
+) -> Result<C::Curve, MsmError> {
+    check_lengths(points.len(), scalars.len())?;
+    C::try_msm_with_config(&normalize(points), scalars, config)
+}
+
+/// Converts `points` to affine form, batching the inversions per chunk and
+/// spreading the chunks over the rayon pool.
+pub(crate) fn normalize<C: CurveAffine>(points: &[C::Curve]) -> Vec<C> {
+    const CHUNK: usize = 1024;
+
+    let mut ret = vec![C::identity(); points.len()];
+    ret.par_chunks_mut(CHUNK)
+        .zip(points.par_chunks(CHUNK))
+        .for_each(|(ret, points)| C::Curve::batch_normalize(points, ret));
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256;
+    use halo2curves::grumpkin;
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+    use crate::utils::{gen_points_seeded, gen_scalars_seeded};
+
+    fn check_projective<C: Msm>() {
+        for n in [0, 5, 3000] {
+            let points = gen_points_seeded::<C>(n, 16)
+                .iter()
+                .map(|point| point.to_curve().double())
+                .collect::<Vec<_>>();
+            let scalars = gen_scalars_seeded(n, 16);
+
+            let affine = normalize::<C>(&points);
+            let mut expected = vec![C::identity(); n];
+            C::Curve::batch_normalize(&points, &mut expected);
+            assert_eq!(affine, expected);
+
+            assert_eq!(
+                C::msm_projective(&points, &scalars),
+                C::msm(&affine, &scalars),
+                "n = {}",
+                n
+            );
+        }
+
+        assert_eq!(
+            C::try_msm_projective(&[C::Curve::generator()], &[]),
+            Err(MsmError::LengthMismatch {
+                points: 1,
+                scalars: 0
+            })
+        );
+    }
+
+    #[test]
+    fn projective_matches_msm() {
+        check_projective::<bn256::G1Affine>();
+        check_projective::<grumpkin::G1Affine>();
+        check_projective::<pallas::Affine>();
+        check_projective::<vesta::Affine>();
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use halo2curves::ff::PrimeField;
+use halo2curves::group::Group;
+use halo2curves::CurveAffine;
+
+/// Default number of points below which MSMs are computed in Rust.
+pub const DEFAULT_SMALL_MSM_THRESHOLD: usize = 8;
+
+static SMALL_MSM_THRESHOLD: AtomicUsize =
+    AtomicUsize::new(DEFAULT_SMALL_MSM_THRESHOLD);
+
+/// Returns the number of points below which MSMs bypass Pippenger, for
+/// calls that do not set their own.
+pub fn small_msm_threshold() -> usize {
+    SMALL_MSM_THRESHOLD.load(Ordering::Relaxed)
+}
+
+/// Sets the number of points below which MSMs bypass Pippenger and are
+/// served by a windowed double-and-add in Rust instead, for calls that do
+/// not set their own.
+pub fn set_small_msm_threshold(threshold: usize) {
+    SMALL_MSM_THRESHOLD.store(threshold, Ordering::Relaxed);
+}
+
+const WINDOW: usize = 4;
+
+/// Fixed-window multiplication with doublings shared across all points.
+/// Each point gets a table of its first `2^WINDOW` multiples, after which
+/// every window costs `WINDOW` doublings and at most one addition per point.
+pub(crate) fn msm<C: CurveAffine>(
+    points: &[C],
+    scalars: &[C::Scalar],
+) -> C::Curve {
+    let tables = points
+        .iter()
+        .map(|point| {
+            let mut table = [C::Curve::identity(); 1 << WINDOW];
+            for i in 1..table.len() {
+                table[i] = table[i - 1] + point;
+            }
+            table
+        })
+        .collect::<Vec<_>>();
+    let reprs = scalars.iter().map(|s| s.to_repr()).collect::<Vec<_>>();
+
+    let nwindows = (C::Scalar::NUM_BITS as usize + WINDOW - 1) / WINDOW;
+    let mut acc = C::Curve::identity();
+    for window in (0..nwindows).rev() {
+        for _ in 0..WINDOW {
+            acc = acc.double();
+        }
+        let (byte, shift) = (window * WINDOW / 8, window * WINDOW % 8);
+        for (table, repr) in tables.iter().zip(&reprs) {
+            let digit =
+                (repr.as_ref()[byte] >> shift) as usize & ((1 << WINDOW) - 1);
+            if digit != 0 {
+                acc += table[digit];
+            }
+        }
+    }
+    acc
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256;
+    use halo2curves::group::Curve;
+    use halo2curves::grumpkin;
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+    use crate::utils::{
+        gen_points_seeded, gen_scalars_seeded, naive_multiscalar_mul,
+        test_seed, TEST_SEED_VAR,
+    };
+    use crate::{Msm, MsmConfig};
+
+    fn check_threshold<C: Msm>() {
+        let seed = test_seed();
+        let points = gen_points_seeded::<C>(102, seed);
+        let scalars = gen_scalars_seeded::<C::Scalar>(102, seed);
+        for threshold in [0, 1, 2, DEFAULT_SMALL_MSM_THRESHOLD, 32, 100] {
+            let config = MsmConfig {
+                small_msm_threshold: Some(threshold),
+                ..Default::default()
+            };
+            for n in threshold.saturating_sub(1)..=threshold + 1 {
+                let msg = format!(
+                    "{}={}, threshold = {}, n = {}",
+                    TEST_SEED_VAR, seed, threshold, n
+                );
+                let (points, scalars) = (&points[..n], &scalars[..n]);
+                assert_eq!(config.is_small(n), n == 0 || n < threshold);
+                assert_eq!(
+                    C::try_msm_with_config(points, scalars, &config)
+                        .map(|ret| ret.to_affine()),
+                    Ok(naive_multiscalar_mul(points, scalars)),
+                    "{}",
+                    msg
+                );
+                assert_eq!(
+                    C::try_batch_msm_affine_with_config(
+                        points,
+                        &[scalars],
+                        &config
+                    ),
+                    Ok(vec![naive_multiscalar_mul(points, scalars)]),
+                    "{}",
+                    msg
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn small_msm_threshold_boundary() {
+        check_threshold::<bn256::G1Affine>();
+        check_threshold::<grumpkin::G1Affine>();
+        check_threshold::<pallas::Affine>();
+        check_threshold::<vesta::Affine>();
+    }
+}
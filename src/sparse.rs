@@ -0,0 +1,200 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use halo2curves::ff::PrimeField;
+use halo2curves::group::Group;
+use rayon::prelude::*;
+
+use crate::error::check_lengths;
+use crate::{Msm, MsmConfig, MsmError};
+
+/// Upper bounds, in bits, of the scalar classes given their own MSM. Ones
+/// are not among them, their points are simply summed up.
+const TIERS: [usize; 4] = [8, 16, 32, 64];
+
+/// Narrow scalars of one class, encoded with just enough bytes for the
+/// widest of them.
+struct Tier<C> {
+    points: Vec<C>,
+    scalars: Vec<u8>,
+    nbits: usize,
+}
+
+/// MSM that skips zero scalars and splits the rest by bit length, so that
+/// narrow scalars are processed with proportionally fewer windows and only
+/// full-width ones go through the regular path.
+pub(crate) fn try_msm<C: Msm>(
+    points: &[C],
+    scalars: &[C::Scalar],
+    config: &MsmConfig,
+) -> Result<C::Curve, MsmError> {
+    check_lengths(points.len(), scalars.len())?;
+    config.check_points(points)?;
+    let config = &MsmConfig {
+        validate: false,
+        ..*config
+    };
+
+    let reprs = scalars.par_iter().map(|s| s.to_repr()).collect::<Vec<_>>();
+    let nbits = reprs
+        .par_iter()
+        .map(|repr| bit_length(repr.as_ref()))
+        .collect::<Vec<_>>();
+
+    let mut tiers = TIERS
+        .iter()
+        .map(|_| Tier {
+            points: vec![],
+            scalars: vec![],
+            nbits: 0,
+        })
+        .collect::<Vec<_>>();
+    for &n in nbits.iter().filter(|&&n| n > 1) {
+        if let Some(tier) = TIERS.iter().position(|&bound| n <= bound) {
+            tiers[tier].nbits = tiers[tier].nbits.max(n);
+        }
+    }
+
+    let mut wide_points = vec![];
+    let mut wide_scalars = vec![];
+    for (i, &n) in nbits.iter().enumerate() {
+        if n <= 1 {
+            continue;
+        }
+        match TIERS.iter().position(|&bound| n <= bound) {
+            Some(tier) => {
+                let tier = &mut tiers[tier];
+                let nbytes = (tier.nbits + 7) / 8;
+                tier.points.push(points[i]);
+                tier.scalars.extend_from_slice(&reprs[i].as_ref()[..nbytes]);
+            }
+            None => {
+                wide_points.push(points[i]);
+                wide_scalars.push(scalars[i]);
+            }
+        }
+    }
+
+    let mut ret = points
+        .par_iter()
+        .zip(nbits.par_iter())
+        .filter(|(_, &n)| n == 1)
+        .fold(C::Curve::identity, |acc, (point, _)| acc + point)
+        .reduce(C::Curve::identity, |a, b| a + b);
+    ret += C::try_msm_with_config(&wide_points, &wide_scalars, config)?;
+    for tier in tiers {
+        ret += C::try_msm_bytes_with_config(
+            &tier.points,
+            &tier.scalars,
+            tier.nbits,
+            config,
+        )?;
+    }
+    Ok(ret)
+}
+
+/// Position of the highest set bit of a little-endian encoding, plus one.
+pub(crate) fn bit_length(repr: &[u8]) -> usize {
+    repr.iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |i| 8 * i + 8 - repr[i].leading_zeros() as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256;
+    use halo2curves::ff::Field;
+    use halo2curves::grumpkin;
+    use pasta_curves::{pallas, vesta};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    use super::*;
+    use crate::utils::{
+        gen_points_seeded, gen_scalars_seeded, test_seed, TEST_SEED_VAR,
+    };
+
+    fn check_sparse<C: Msm>() {
+        let seed = test_seed();
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let points = gen_points_seeded::<C>(500, seed);
+        let scalars = gen_scalars_seeded::<C::Scalar>(500, seed)
+            .into_iter()
+            .enumerate()
+            .map(|(i, scalar)| match i % 7 {
+                0 | 1 => C::Scalar::ZERO,
+                2 => C::Scalar::ONE,
+                3 => C::Scalar::from(rng.gen_range(2..256)),
+                4 => C::Scalar::from(rng.gen::<u32>() as u64),
+                5 => C::Scalar::from(rng.gen::<u64>()),
+                _ => scalar,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            C::msm_sparse(&points, &scalars),
+            C::msm(&points, &scalars),
+            "{}={}",
+            TEST_SEED_VAR,
+            seed
+        );
+        assert_eq!(
+            C::msm_sparse(&points[..2], &scalars[..2]),
+            C::Curve::identity()
+        );
+        assert_eq!(C::msm_sparse(&[], &[]), C::Curve::identity());
+    }
+
+    /// Few points per tier, with every tier as wide as its bound, so that
+    /// the last window of each ends right at the end of its scalars.
+    fn check_sparse_small<C: Msm>() {
+        let seed = test_seed();
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let points = gen_points_seeded::<C>(32, seed);
+        for n in [1, 2, 3, 7, 8, 31, 32] {
+            for nbits in [1, 2].into_iter().chain(TIERS) {
+                let scalars = (0..n)
+                    .map(|_| {
+                        rng.gen::<u64>() >> (64 - nbits) | 1 << (nbits - 1)
+                    })
+                    .map(C::Scalar::from)
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    C::msm_sparse(&points[..n], &scalars),
+                    C::msm(&points[..n], &scalars),
+                    "n = {}, nbits = {}, {}={}",
+                    n,
+                    nbits,
+                    TEST_SEED_VAR,
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sparse_small() {
+        check_sparse_small::<bn256::G1Affine>();
+        check_sparse_small::<grumpkin::G1Affine>();
+        check_sparse_small::<pallas::Affine>();
+        check_sparse_small::<vesta::Affine>();
+    }
+
+    #[test]
+    fn sparse_matches_msm() {
+        check_sparse::<bn256::G1Affine>();
+        check_sparse::<grumpkin::G1Affine>();
+        check_sparse::<pallas::Affine>();
+        check_sparse::<vesta::Affine>();
+    }
+
+    #[test]
+    fn bit_lengths() {
+        assert_eq!(bit_length(&[0, 0, 0]), 0);
+        assert_eq!(bit_length(&[1, 0, 0]), 1);
+        assert_eq!(bit_length(&[0xff, 0, 0]), 8);
+        assert_eq!(bit_length(&[0, 1, 0]), 9);
+        assert_eq!(bit_length(&[0, 0, 0x80]), 24);
+    }
+}
@@ -0,0 +1,40 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+#ifndef __GRUMPKIN_MSM_THREAD_POOLS_HPP__
+#define __GRUMPKIN_MSM_THREAD_POOLS_HPP__
+
+#include <map>
+#include <memory>
+#include <mutex>
+
+#include <util/thread_pool_t.hpp>
+
+/*
+ * Pool to run an MSM on, given the thread count requested by the caller:
+ * none (i.e. the calling thread alone) for 1, |da_pool| for 0, otherwise
+ * a pool of exactly |nthreads| threads, created on first use and shared
+ * by all later calls asking for the same count. Counts are capped at the
+ * size of |da_pool|, i.e. the number of available CPUs, from which on
+ * |da_pool| itself is used. The pools live as long as the process, so
+ * this caps them at one per count below that.
+ */
+static thread_pool_t* select_pool(thread_pool_t& da_pool, size_t nthreads)
+{
+    if (nthreads == 1)
+        return nullptr;
+    if (nthreads == 0 || nthreads >= da_pool.size())
+        return &da_pool;
+
+    static std::mutex mtx;
+    static std::map<size_t, std::unique_ptr<thread_pool_t>> pools;
+
+    std::lock_guard<std::mutex> lock(mtx);
+    auto& pool = pools[nthreads];
+    if (!pool)
+        pool.reset(new thread_pool_t((unsigned int)nthreads));
+    return pool.get();
+}
+
+#endif
@@ -1,96 +1,270 @@
-use core::mem::transmute;
-use core::sync::atomic::*;
-use halo2curves::bn256;
-use halo2curves::ff::Field;
-use halo2curves::group::Curve;
-use halo2curves::CurveExt;
+use halo2curves::ff::{Field, PrimeField};
+use halo2curves::group::{Curve, Group};
+use halo2curves::{CurveAffine, CurveExt};
 use rand::{RngCore, SeedableRng};
 use rand_chacha::ChaCha20Rng;
-use rayon::iter::{
-    IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator,
-};
-use std::sync::{Arc, Mutex};
+use rayon::prelude::*;
 
-pub fn gen_points(npoints: usize) -> Vec<bn256::G1Affine> {
-    let ret = vec![bn256::G1Affine::default(); npoints];
+/// Environment variable that fixes the seed returned by [`test_seed`].
+pub const TEST_SEED_VAR: &str = "MSM_TEST_SEED";
+
+/// Seed for randomized tests: the value of `MSM_TEST_SEED` if set and a
+/// fresh random one otherwise. Tests put it in their assertion messages as
+/// `MSM_TEST_SEED=<seed>`, ready to rerun a failure with.
+pub fn test_seed() -> u64 {
+    match std::env::var(TEST_SEED_VAR) {
+        Ok(seed) => seed.parse().unwrap_or_else(|_| {
+            panic!("{} must be a u64, got {:?}", TEST_SEED_VAR, seed)
+        }),
+        Err(_) => ChaCha20Rng::from_entropy().next_u64(),
+    }
+}
+
+/// Generates `npoints` random points of any supported curve, e.g.
+/// `gen_points::<grumpkin::G1Affine>(n)`.
+pub fn gen_points<C: CurveAffine>(npoints: usize) -> Vec<C> {
+    gen_points_seeded(npoints, ChaCha20Rng::from_entropy().next_u64())
+}
+
+/// Like [`gen_points`], but deterministic in `seed`.
+pub fn gen_points_seeded<C: CurveAffine>(npoints: usize, seed: u64) -> Vec<C> {
+    const CHUNK: usize = 1024;
 
     let mut rnd = vec![0u8; 32 * npoints];
-    ChaCha20Rng::from_entropy().fill_bytes(&mut rnd);
-
-    let n_workers = rayon::current_num_threads();
-    let work = AtomicUsize::new(0);
-    rayon::scope(|s| {
-        for _ in 0..n_workers {
-            s.spawn(|_| {
-                let hash = bn256::G1::hash_to_curve("foobar");
-
-                let mut stride = 1024;
-                let mut tmp = vec![bn256::G1::default(); stride];
-
-                loop {
-                    let work = work.fetch_add(stride, Ordering::Relaxed);
-                    if work >= npoints {
-                        break;
-                    }
-                    if work + stride > npoints {
-                        stride = npoints - work;
-                        unsafe { tmp.set_len(stride) };
-                    }
-                    for (i, point) in tmp.iter_mut().enumerate().take(stride) {
-                        let off = (work + i) * 32;
-                        *point = hash(&rnd[off..off + 32]);
-                    }
-                    #[allow(mutable_transmutes)]
-                    bn256::G1::batch_normalize(&tmp, unsafe {
-                        transmute::<&[bn256::G1Affine], &mut [bn256::G1Affine]>(
-                            &ret[work..work + stride],
-                        )
-                    });
-                }
-            })
+    ChaCha20Rng::seed_from_u64(seed).fill_bytes(&mut rnd);
+
+    let mut ret = vec![C::identity(); npoints];
+    ret.par_chunks_mut(CHUNK)
+        .zip(rnd.par_chunks(32 * CHUNK))
+        .for_each(|(ret, rnd)| {
+            let hash = C::CurveExt::hash_to_curve("foobar");
+            let tmp = rnd.chunks(32).map(&hash).collect::<Vec<_>>();
+            C::CurveExt::batch_normalize(&tmp, ret);
+        });
+
+    ret
+}
+
+/// Generates `npoints` random elements of any field, e.g.
+/// `gen_scalars::<grumpkin::Fr>(n)`.
+pub fn gen_scalars<F: Field>(npoints: usize) -> Vec<F> {
+    gen_scalars_seeded(npoints, ChaCha20Rng::from_entropy().next_u64())
+}
+
+/// Like [`gen_scalars`], but deterministic in `seed`. Every chunk of
+/// elements is drawn from its own stream of the seeded generator, so the
+/// result does not depend on how the chunks are spread over threads.
+pub fn gen_scalars_seeded<F: Field>(npoints: usize, seed: u64) -> Vec<F> {
+    const CHUNK: usize = 1024;
+
+    let mut ret = vec![F::ZERO; npoints];
+    ret.par_chunks_mut(CHUNK).enumerate().for_each(|(i, ret)| {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        rng.set_stream(i as u64 + 1);
+        for scalar in ret {
+            *scalar = F::random(&mut rng);
         }
     });
 
     ret
 }
 
-pub fn gen_scalars(npoints: usize) -> Vec<bn256::Fr> {
-    let ret = Arc::new(Mutex::new(vec![bn256::Fr::default(); npoints]));
-
-    let n_workers = rayon::current_num_threads();
-    let work = Arc::new(AtomicUsize::new(0));
-
-    rayon::scope(|s| {
-        for _ in 0..n_workers {
-            let ret_clone = Arc::clone(&ret);
-            let work_clone = Arc::clone(&work);
-
-            s.spawn(move |_| {
-                let mut rng = ChaCha20Rng::from_entropy();
-                loop {
-                    let work = work_clone.fetch_add(1, Ordering::Relaxed);
-                    if work >= npoints {
-                        break;
-                    }
-                    let mut ret = ret_clone.lock().unwrap();
-                    ret[work] = bn256::Fr::random(&mut rng);
-                }
-            });
+/// Scalars at the edges of the Pippenger windows: zero, one, the largest
+/// element, `(p - 1) / 2` and `(p + 1) / 2`, every power of two below the
+/// modulus along with its predecessor and negation, and repeating byte
+/// patterns just below the top bit. The negations fill the top windows with
+/// high digits, and the patterns put digits around half a window in every
+/// window, which makes a signed-digit recoding borrow throughout.
+pub fn gen_edge_scalars<F: PrimeField>() -> Vec<F> {
+    let nbits = F::NUM_BITS as usize - 1;
+
+    let mut ret =
+        vec![F::ZERO, F::ONE, -F::ONE, F::TWO_INV - F::ONE, F::TWO_INV];
+    let mut pow = F::ONE.double();
+    for _ in 1..=nbits {
+        ret.extend([pow, pow - F::ONE, -pow]);
+        pow = pow.double();
+    }
+    for pattern in [0xff, 0x80, 0x7f, 0x88, 0xf0, 0x0f, 0xaa, 0x55] {
+        let mut repr = F::Repr::default();
+        for (i, byte) in repr.as_mut().iter_mut().enumerate() {
+            let bits = nbits.saturating_sub(8 * i).min(8);
+            *byte = pattern & ((1u16 << bits) - 1) as u8;
         }
-    });
+        ret.push(F::from_repr(repr).unwrap());
+    }
 
-    Arc::try_unwrap(ret).unwrap().into_inner().unwrap()
+    ret
 }
 
-pub fn naive_multiscalar_mul(
-    points: &[bn256::G1Affine],
-    scalars: &[bn256::Fr],
-) -> bn256::G1Affine {
-    let ret: bn256::G1 = points
+/// Computes the MSM one scalar multiplication at a time, as a reference.
+pub fn naive_multiscalar_mul<C: CurveAffine>(
+    points: &[C],
+    scalars: &[C::Scalar],
+) -> C {
+    let ret: C::CurveExt = points
         .par_iter()
         .zip_eq(scalars.par_iter())
-        .map(|(p, s)| p * s)
+        .map(|(p, s)| *p * s)
         .sum();
 
     ret.to_affine()
 }
+
+/// Moves `point` off the curve by bumping its `x` coordinate. Both curve
+/// libraries store `x` first, as relied upon by the C++ backend.
+#[cfg(test)]
+pub(crate) fn off_curve<C: CurveAffine>(point: C) -> C {
+    let mut ret = point;
+    let x = *point.coordinates().unwrap().x() + C::Base::ONE;
+    unsafe { *(&mut ret as *mut C as *mut C::Base) = x };
+    ret
+}
+
+/// Copy of a slice placed right before an inaccessible page, so that any
+/// read past its end faults.
+#[cfg(all(test, unix))]
+pub(crate) struct Guarded<T> {
+    map: *mut libc::c_void,
+    map_len: usize,
+    data: *const T,
+    len: usize,
+}
+
+#[cfg(all(test, unix))]
+impl<T: Copy> Guarded<T> {
+    pub(crate) fn new(data: &[T]) -> Self {
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let size = std::mem::size_of_val(data);
+        let map_len = (size + page - 1) / page * page + page;
+        unsafe {
+            let map = libc::mmap(
+                std::ptr::null_mut(),
+                map_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            assert_ne!(map, libc::MAP_FAILED);
+            let guard = (map as *mut u8).add(map_len - page);
+            assert_eq!(libc::mprotect(guard as *mut libc::c_void, page, 0), 0);
+            let ptr = guard.sub(size) as *mut T;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+            Self {
+                map,
+                map_len,
+                data: ptr,
+                len: data.len(),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+impl<T> std::ops::Deref for Guarded<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.data, self.len) }
+    }
+}
+
+#[cfg(all(test, unix))]
+impl<T> Drop for Guarded<T> {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.map, self.map_len) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::{bn256, grumpkin};
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+
+    fn check_generators<C: CurveAffine>() {
+        let seed = test_seed();
+        let points = gen_points_seeded::<C>(2000, seed);
+        let scalars = gen_scalars_seeded::<C::Scalar>(2000, seed);
+        let msg = format!("{}={}", TEST_SEED_VAR, seed);
+
+        assert_eq!(points, gen_points_seeded::<C>(2000, seed), "{}", msg);
+        assert_eq!(
+            scalars,
+            gen_scalars_seeded::<C::Scalar>(2000, seed),
+            "{}",
+            msg
+        );
+        assert_ne!(points, gen_points_seeded::<C>(2000, seed ^ 1), "{}", msg);
+        assert_eq!(
+            scalars[..100],
+            gen_scalars_seeded(100, seed)[..],
+            "{}",
+            msg
+        );
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        assert_eq!(
+            points,
+            pool.install(|| gen_points_seeded::<C>(2000, seed)),
+            "{}",
+            msg
+        );
+        assert_eq!(
+            scalars,
+            pool.install(|| gen_scalars_seeded::<C::Scalar>(2000, seed)),
+            "{}",
+            msg
+        );
+
+        assert!(
+            points
+                .iter()
+                .all(|p| bool::from(p.is_on_curve()) && p != &C::identity()),
+            "{}",
+            msg
+        );
+        assert_ne!(points[0], points[1], "{}", msg);
+        assert_ne!(scalars[0], scalars[1], "{}", msg);
+
+        let naive = naive_multiscalar_mul(&points[..3], &scalars[..3]);
+        let expected = points[0] * scalars[0]
+            + points[1] * scalars[1]
+            + points[2] * scalars[2];
+        assert_eq!(naive, expected.to_affine(), "{}", msg);
+    }
+
+    fn check_edge_scalars<F: PrimeField>() {
+        let scalars = gen_edge_scalars::<F>();
+        assert_eq!(scalars, gen_edge_scalars::<F>());
+        assert_eq!(scalars.len(), 5 + 3 * (F::NUM_BITS as usize - 1) + 8);
+
+        let top = F::from(2).pow_vartime([F::NUM_BITS as u64 - 1]);
+        for expected in [F::ZERO, F::ONE, -F::ONE, top, -top, top - F::ONE] {
+            assert!(scalars.contains(&expected));
+        }
+        assert_eq!(scalars[3].double(), -F::ONE);
+        assert_eq!(scalars[scalars.len() - 8], top - F::ONE);
+    }
+
+    #[test]
+    fn edge_scalars() {
+        check_edge_scalars::<bn256::Fr>();
+        check_edge_scalars::<grumpkin::Fr>();
+        check_edge_scalars::<pallas::Scalar>();
+        check_edge_scalars::<vesta::Scalar>();
+    }
+
+    #[test]
+    fn generators() {
+        check_generators::<bn256::G1Affine>();
+        check_generators::<grumpkin::G1Affine>();
+        check_generators::<pallas::Affine>();
+        check_generators::<vesta::Affine>();
+    }
+}
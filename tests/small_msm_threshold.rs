@@ -0,0 +1,58 @@
+// Copyright Supranational LLC
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The process-wide small-MSM threshold, kept in a binary of its own so that
+//! setting it cannot change the code paths of concurrently running tests.
+
+use grumpkin_msm::utils::{
+    gen_points_seeded, gen_scalars_seeded, naive_multiscalar_mul, test_seed,
+    TEST_SEED_VAR,
+};
+use grumpkin_msm::{
+    set_small_msm_threshold, small_msm_threshold, Msm,
+    DEFAULT_SMALL_MSM_THRESHOLD,
+};
+use halo2curves::group::Curve;
+use halo2curves::{bn256, grumpkin};
+use pasta_curves::{pallas, vesta};
+
+/// Restores the default threshold when dropped, even if a check panics.
+struct Restore;
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        set_small_msm_threshold(DEFAULT_SMALL_MSM_THRESHOLD);
+    }
+}
+
+fn check_threshold<C: Msm>(threshold: usize) {
+    let seed = test_seed();
+    let points = gen_points_seeded::<C>(threshold + 2, seed);
+    let scalars = gen_scalars_seeded::<C::Scalar>(threshold + 2, seed);
+    for n in threshold.saturating_sub(1)..=threshold + 1 {
+        assert_eq!(
+            C::msm(&points[..n], &scalars[..n]).to_affine(),
+            naive_multiscalar_mul(&points[..n], &scalars[..n]),
+            "{}={}, threshold = {}, n = {}",
+            TEST_SEED_VAR,
+            seed,
+            threshold,
+            n
+        );
+    }
+}
+
+#[test]
+fn global_threshold() {
+    let _restore = Restore;
+    assert_eq!(small_msm_threshold(), DEFAULT_SMALL_MSM_THRESHOLD);
+    for threshold in [32, 2, 0] {
+        set_small_msm_threshold(threshold);
+        assert_eq!(small_msm_threshold(), threshold);
+        check_threshold::<bn256::G1Affine>(threshold);
+        check_threshold::<grumpkin::G1Affine>(threshold);
+        check_threshold::<pallas::Affine>(threshold);
+        check_threshold::<vesta::Affine>(threshold);
+    }
+}